@@ -0,0 +1,132 @@
+use crate::types::WatchStatus;
+use crate::{Error, Result};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+/// One anime on a users list, with the MAL id so the list also covers animes we only know by MAL id
+#[derive(Debug)]
+pub struct ListEntry {
+    pub anilist_id: i32,
+    pub mal_id: Option<i32>,
+    pub status: WatchStatus,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+enum MediaListStatus {
+    Current,
+    Planning,
+    Completed,
+    Dropped,
+    Paused,
+    Repeating,
+}
+
+impl From<MediaListStatus> for WatchStatus {
+    fn from(status: MediaListStatus) -> Self {
+        match status {
+            MediaListStatus::Current | MediaListStatus::Repeating => WatchStatus::Watching,
+            MediaListStatus::Planning => WatchStatus::Planning,
+            MediaListStatus::Completed => WatchStatus::Completed,
+            MediaListStatus::Dropped => WatchStatus::Dropped,
+            MediaListStatus::Paused => WatchStatus::Paused,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ListMedia {
+    id: i32,
+    #[serde(rename = "idMal")]
+    id_mal: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct MediaListEntry {
+    status: Option<MediaListStatus>,
+    media: ListMedia,
+}
+
+#[derive(Deserialize)]
+struct MediaListGroup {
+    entries: Vec<MediaListEntry>,
+}
+
+#[derive(Deserialize)]
+struct MediaListCollection {
+    lists: Vec<MediaListGroup>,
+}
+
+#[derive(Deserialize)]
+struct CollectionData {
+    #[serde(rename = "MediaListCollection")]
+    collection: Option<MediaListCollection>,
+}
+
+#[derive(Deserialize)]
+struct CollectionResponse {
+    data: Option<CollectionData>,
+}
+
+/// Fetches the public anime list of an AniList user, NotFound if there is no such user
+pub async fn fetch_user_list(username: &String) -> Result<Vec<ListEntry>> {
+    let json_body = json!({
+        "query": LIST_QUERY_STRING,
+        "variables": {
+            "userName": username,
+        }
+    });
+
+    let response = Client::new()
+        .post("https://graphql.anilist.co")
+        .json(&json_body)
+        .send()
+        .await?;
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(Error::NotFound);
+    }
+    if !response.status().is_success() {
+        return Err(Error::BadRequest {
+            url: "https://graphql.anilist.co".to_string(),
+            status_code: response.status(),
+        });
+    }
+
+    let collection = response
+        .json::<CollectionResponse>()
+        .await?
+        .data
+        .and_then(|d| d.collection)
+        .ok_or(Error::NotFound)?;
+
+    Ok(collection
+        .lists
+        .into_iter()
+        .flat_map(|list| list.entries)
+        .filter_map(|entry| {
+            Some(ListEntry {
+                anilist_id: entry.media.id,
+                mal_id: entry.media.id_mal,
+                status: entry.status?.into(),
+            })
+        })
+        .collect())
+}
+
+const LIST_QUERY_STRING: &str = r#"
+query ($userName: String) {
+    MediaListCollection(userName: $userName, type: ANIME) {
+        lists {
+            entries {
+                status
+                media {
+                    id
+                    idMal
+                }
+            }
+        }
+    }
+}
+"#;
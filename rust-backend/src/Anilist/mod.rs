@@ -1,2 +1,3 @@
+pub mod lists;
 pub mod types;
 pub use types::Media;
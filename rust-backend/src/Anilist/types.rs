@@ -1,4 +1,5 @@
 use crate::Result;
+use log::warn;
 use num_enum::TryFromPrimitive;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -31,6 +32,12 @@ pub struct MediaTitle {
 #[sqlx(transparent)]
 pub struct ImageURL(URL);
 
+impl ImageURL {
+    pub fn as_str(&self) -> &str {
+        &self.0.0
+    }
+}
+
 #[derive(
     Debug, PartialEq, Eq, PartialOrd, Ord, Hash, FromRow, Deserialize, Serialize, Type, Clone,
 )]
@@ -153,6 +160,54 @@ pub struct Media {
     pub season: Option<ReleaseSeason>,
     #[serde(rename = "seasonYear")]
     pub season_year: Option<i32>,
+    pub relations: Option<MediaConnection>,
+}
+
+#[derive(Deserialize, Serialize, FromRow, Debug)]
+pub struct RelatedMedia {
+    pub id: i32,
+    #[serde(rename = "type")]
+    pub media_type: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, FromRow, Debug)]
+pub struct MediaEdge {
+    #[serde(rename = "relationType")]
+    pub relation_type: Option<String>,
+    pub node: Option<RelatedMedia>,
+}
+
+#[derive(Deserialize, Serialize, FromRow, Debug)]
+pub struct MediaConnection {
+    pub edges: Vec<MediaEdge>,
+}
+
+impl Media {
+    /// Relation types that keep two animes in the same franchise, spin offs and adaptations start their own
+    const FRANCHISE_RELATIONS: [&str; 6] = [
+        "PREQUEL",
+        "SEQUEL",
+        "PARENT",
+        "SIDE_STORY",
+        "SUMMARY",
+        "ALTERNATIVE",
+    ];
+
+    /// AniList ids of the other animes in the same franchise that this media links to directly
+    pub fn franchise_relations(&self) -> Vec<i32> {
+        self.relations
+            .iter()
+            .flat_map(|r| r.edges.iter())
+            .filter(|edge| {
+                edge.relation_type
+                    .as_ref()
+                    .is_some_and(|t| Self::FRANCHISE_RELATIONS.contains(&t.as_str()))
+            })
+            .filter_map(|edge| edge.node.as_ref())
+            .filter(|node| node.media_type.as_deref() == Some("ANIME"))
+            .map(|node| node.id)
+            .collect()
+    }
 }
 
 impl Media {
@@ -188,11 +243,10 @@ impl Media {
                 .post("https://graphql.anilist.co")
                 .json(&json_body)
                 .send()
-                .await
-                .unwrap();
+                .await?;
 
             if response.status().is_success() {
-                let data: AnilistResponse = response.json().await.unwrap();
+                let data: AnilistResponse = response.json().await?;
                 all_media.extend(data.data.page.media);
 
                 if data.data.page.page_info.is_none_or(|a| !a.has_next_page) {
@@ -200,7 +254,10 @@ impl Media {
                 }
                 page += 1;
             } else {
-                println!("{}", response.text().await.unwrap());
+                warn!(
+                    "AniList request failed: {}",
+                    response.text().await.unwrap_or_default()
+                );
                 break;
             }
         }
@@ -274,6 +331,15 @@ query ($ids: [Int] = [170695], $isMain: Boolean = true, $version: Int = 3, $page
 			episodes
     season
     seasonYear
+    relations {
+      edges {
+        relationType(version: 2)
+        node {
+          id
+          type
+        }
+      }
+    }
   }
   pageInfo {
     hasNextPage
@@ -281,3 +347,43 @@ query ($ids: [Int] = [170695], $isMain: Boolean = true, $version: Int = 3, $page
 }
 }
 "#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_franchise_relations() {
+        let media: Media = serde_json::from_value(json!({
+            "id": 1,
+            "title": { "romaji": null, "english": null, "native": null },
+            "meanScore": 80,
+            "relations": {
+                "edges": [
+                    { "relationType": "SEQUEL", "node": { "id": 2, "type": "ANIME" } },
+                    { "relationType": "PREQUEL", "node": { "id": 3, "type": "ANIME" } },
+                    { "relationType": "SPIN_OFF", "node": { "id": 4, "type": "ANIME" } },
+                    { "relationType": "ADAPTATION", "node": { "id": 5, "type": "MANGA" } },
+                    { "relationType": "SIDE_STORY", "node": { "id": 6, "type": "MANGA" } },
+                    { "relationType": "PARENT", "node": null },
+                    { "relationType": null, "node": { "id": 7, "type": "ANIME" } }
+                ]
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(media.franchise_relations(), vec![2, 3]);
+    }
+
+    #[test]
+    fn no_relations_without_relations() {
+        let media: Media = serde_json::from_value(json!({
+            "id": 1,
+            "title": { "romaji": null, "english": null, "native": null },
+            "meanScore": 80
+        }))
+        .unwrap();
+
+        assert!(media.franchise_relations().is_empty());
+    }
+}
@@ -2,7 +2,9 @@ use crate::{
     Result,
     anilist::types::AnilistID,
     database::regex_search::process_artist_name,
-    japanese_processing::{normalize_text, process_possible_japanese, process_similarity},
+    japanese_processing::{
+        TextVariants, normalize_text, normalized_similarity, process_possible_japanese, search_form,
+    },
     spotify::responses::TrackObject,
 };
 use core::f32;
@@ -13,6 +15,7 @@ use itertools::Itertools;
 use log::{error, warn};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 pub struct AnisongClient {
     client: Client,
@@ -21,6 +24,9 @@ pub struct AnisongClient {
 impl AnisongClient {
     const SEARCH_REQUEST_URL: &str = "https://anisongdb.com/api/search_request";
     const ARTIST_ID_SEARCH_REQUEST_URL: &str = "https://anisongdb.com/api/artist_ids_request";
+    const COMPOSER_ID_SEARCH_REQUEST_URL: &str = "https://anisongdb.com/api/composer_ids_request";
+    const ANN_ID_SEARCH_REQUEST_URL: &str = "https://anisongdb.com/api/annId_request";
+    const MAL_IDS_SEARCH_REQUEST_URL: &str = "https://anisongdb.com/api/malIDs_request";
 
     pub fn new() -> Self {
         Self {
@@ -61,12 +67,12 @@ impl AnisongClient {
             .await?;
 
         match response.status() {
-            value if value.is_success() => Ok(response.json().await.unwrap()),
+            value if value.is_success() => Ok(response.json().await?),
             status::StatusCode::SERVICE_UNAVAILABLE | status::StatusCode::INTERNAL_SERVER_ERROR => {
                 warn!(
                     "Non-successfull response from anisong, status: {} Response:\n{}",
                     response.status(),
-                    response.text().await.unwrap(),
+                    response.text().await.unwrap_or_default(),
                 );
                 Ok(vec![])
             }
@@ -74,7 +80,131 @@ impl AnisongClient {
                 error!(
                     "Unrecognised non-successfull response from anisong, treated as empty response, status: {} Response:\n{}",
                     response.status(),
-                    response.text().await.unwrap(),
+                    response.text().await.unwrap_or_default(),
+                );
+                Ok(vec![])
+            }
+        }
+    }
+
+    /// Songs composed by, or if arrangement is set also arranged by, any of the given artists
+    pub async fn get_animes_by_composers_ids(
+        &self,
+        ids: Vec<i32>,
+        arrangement: bool,
+    ) -> Result<Vec<Anime>> {
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let search = ComposerIDSearchRequest {
+            composer_ids: ids,
+            arrangement,
+            ignore_duplicate: false,
+            opening_filter: true,
+            ending_filter: true,
+            insert_filter: true,
+            normal_broadcast: true,
+            dub: true,
+            rebroadcast: true,
+            standard: true,
+            instrumental: true,
+            chanting: true,
+            character: true,
+        };
+
+        let response = self
+            .client
+            .post(Self::COMPOSER_ID_SEARCH_REQUEST_URL)
+            .json(&search)
+            .send()
+            .await?;
+
+        match response.status() {
+            value if value.is_success() => Ok(response.json().await?),
+            status => {
+                warn!(
+                    "Non-successfull response from anisong, status: {} Response:\n{}",
+                    status,
+                    response.text().await.unwrap_or_default(),
+                );
+                Ok(vec![])
+            }
+        }
+    }
+
+    /// Every song of a single anime
+    pub async fn get_animes_by_ann_id(&self, ann_id: i32) -> Result<Vec<Anime>> {
+        let search = AnnIdSearchRequest {
+            ann_id,
+            ignore_duplicate: false,
+            opening_filter: true,
+            ending_filter: true,
+            insert_filter: true,
+            normal_broadcast: true,
+            dub: true,
+            rebroadcast: true,
+            standard: true,
+            instrumental: true,
+            chanting: true,
+            character: true,
+        };
+
+        let response = self
+            .client
+            .post(Self::ANN_ID_SEARCH_REQUEST_URL)
+            .json(&search)
+            .send()
+            .await?;
+
+        match response.status() {
+            value if value.is_success() => Ok(response.json().await?),
+            status => {
+                warn!(
+                    "Non-successfull response from anisong, status: {} Response:\n{}",
+                    status,
+                    response.text().await.unwrap_or_default(),
+                );
+                Ok(vec![])
+            }
+        }
+    }
+
+    /// Every song of the animes with the given myanimelist ids
+    pub async fn get_animes_by_mal_ids(&self, mal_ids: Vec<i32>) -> Result<Vec<Anime>> {
+        if mal_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let search = MalIdsSearchRequest {
+            mal_ids,
+            ignore_duplicate: false,
+            opening_filter: true,
+            ending_filter: true,
+            insert_filter: true,
+            normal_broadcast: true,
+            dub: true,
+            rebroadcast: true,
+            standard: true,
+            instrumental: true,
+            chanting: true,
+            character: true,
+        };
+
+        let response = self
+            .client
+            .post(Self::MAL_IDS_SEARCH_REQUEST_URL)
+            .json(&search)
+            .send()
+            .await?;
+
+        match response.status() {
+            value if value.is_success() => Ok(response.json().await?),
+            status => {
+                warn!(
+                    "Non-successfull response from anisong, status: {} Response:\n{}",
+                    status,
+                    response.text().await.unwrap_or_default(),
                 );
                 Ok(vec![])
             }
@@ -88,8 +218,7 @@ impl AnisongClient {
     ) -> Result<Vec<Anime>> {
         Ok(self
             .get_animes_by_artists_ids::<true>(artist_ids)
-            .await
-            .unwrap()
+            .await?
             .into_iter()
             .filter(|a| a.songName == song_title)
             .collect::<Vec<Anime>>())
@@ -132,7 +261,7 @@ impl AnisongClient {
             .await?;
 
         match response.status() {
-            status if status.is_success() => Ok(response.json().await.unwrap()),
+            status if status.is_success() => Ok(response.json().await?),
             _ => {
                 warn!("{}", response.text().await?);
                 Ok(vec![])
@@ -187,13 +316,15 @@ impl AnisongClient {
             .post(Self::SEARCH_REQUEST_URL)
             .json(&search)
             .send()
-            .await
-            .unwrap();
+            .await?;
 
         match response.status().is_success() {
-            true => Ok(response.json().await.unwrap()),
+            true => Ok(response.json().await?),
             false => {
-                error!("Anisong fetch {}", response.text().await.unwrap());
+                error!(
+                    "Anisong fetch {}",
+                    response.text().await.unwrap_or_default()
+                );
                 Ok(vec![])
             }
         }
@@ -206,8 +337,7 @@ impl AnisongClient {
             let romanji_artist = process_possible_japanese(&process_artist_name(&artist.name));
             let songs = self
                 .get_animes_by_artist_name(Some(&romanji_artist), Some(&romanji_artist))
-                .await
-                .unwrap();
+                .await?;
             anime_song_entries.extend(songs);
         }
         Ok(anime_song_entries)
@@ -221,9 +351,11 @@ impl AnisongClient {
             return Ok((vec![], 0.0));
         }
 
+        // Same romanized form the database stores in song_name_normalized, so both paths score alike
+        let song_name = search_form(song_name);
         let evaluations: Vec<f32> = animes
             .iter()
-            .map(|a| process_similarity(&song_name, &a.songName))
+            .map(|a| normalized_similarity(&song_name, &TextVariants::new(&a.songName).normalized))
             .collect();
 
         let max_score = evaluations.iter().map(|s| *s).fold(f32::MIN, f32::max);
@@ -249,16 +381,18 @@ impl AnisongClient {
             return Ok((vec![], 0.0));
         }
 
-        let artist_names = artist_names
-            .into_iter()
-            .map(|a| process_artist_name(&a))
-            .join(" ");
+        let artist_names = search_form(
+            &artist_names
+                .into_iter()
+                .map(|a| process_artist_name(&a))
+                .join(" "),
+        );
         let evaluations: Vec<f32> = animes
             .iter()
             .map(|a| {
                 let anisong_artists_names = a.artists.iter().map(|b| &b.names[0]).join(" ");
                 fuzz::token_set_ratio(
-                    &normalize_text(&process_possible_japanese(&artist_names)),
+                    &artist_names,
                     &normalize_text(&anisong_artists_names),
                     true,
                     true,
@@ -280,6 +414,57 @@ impl AnisongClient {
 
         Ok((best_animes, max_score))
     }
+
+    /// Like pick_best_by_artist_names but a candidate also scores on its composers and arrangers,
+    /// since spotify often credits the composer rather than the performer, especially for OSTs
+    pub fn pick_best_by_credited_names<'a>(
+        animes: &mut Vec<Anime>,
+        artist_names: Vec<&String>,
+    ) -> Result<(Vec<Anime>, f32)> {
+        if animes.len() == 0 {
+            return Ok((vec![], 0.0));
+        }
+
+        let artist_names = search_form(
+            &artist_names
+                .into_iter()
+                .map(|a| process_artist_name(&a))
+                .join(" "),
+        );
+        let score = |credited: &Vec<Artist>| {
+            if credited.is_empty() {
+                return 0.0;
+            }
+            fuzz::token_set_ratio(
+                &artist_names,
+                &normalize_text(&credited.iter().map(|b| &b.names[0]).join(" ")),
+                true,
+                true,
+            ) as f32
+        };
+        let evaluations: Vec<f32> = animes
+            .iter()
+            .map(|a| {
+                score(&a.artists)
+                    .max(score(&a.composers))
+                    .max(score(&a.arrangers))
+            })
+            .collect();
+
+        let max_score = evaluations.iter().map(|s| *s).fold(f32::MIN, f32::max);
+
+        let mut best_animes = Vec::new();
+        let mut i = evaluations.len();
+
+        while i > 0 {
+            i -= 1;
+            if evaluations[i] == max_score {
+                best_animes.push(animes.swap_remove(i));
+            }
+        }
+
+        Ok((best_animes, max_score))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Default)]
@@ -392,10 +577,11 @@ pub struct Artist {
     pub members: Option<Vec<Artist>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct AnimeListLinks {
     pub myanimelist: Option<i32>,
     pub anidb: Option<i32>,
+    #[schema(value_type = Option<i32>)]
     pub anilist: Option<AnilistID>,
     pub kitsu: Option<i32>,
 }
@@ -0,0 +1,128 @@
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::http::{HeaderMap, header::AUTHORIZATION};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use tower_sessions::Session;
+
+use crate::{
+    AppState, Error, Result,
+    database::api_tokens::DBApiToken,
+    spotify::api::{EXPIRY_MARGIN, request_refreshed_token, session_access_token, session_user_id},
+    types::ApiScope,
+};
+
+const TOKEN_PREFIX: &str = "wa_";
+
+/// A new API token and the hash of it that gets stored
+pub fn generate_token() -> (String, String) {
+    let random_bytes: [u8; 32] = rand::rng().random();
+    let token = format!("{}{}", TOKEN_PREFIX, hex::encode(random_bytes));
+    let token_hash = hash_token(&token);
+    (token, token_hash)
+}
+
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+}
+
+/// The API token the request was made with, None when it should use the cookie session instead
+async fn api_token(
+    headers: &HeaderMap,
+    app_state: &Arc<AppState>,
+    scope: ApiScope,
+) -> Result<Option<DBApiToken>> {
+    let Some(token) = bearer_token(headers) else {
+        return Ok(None);
+    };
+
+    let api_token = app_state
+        .database
+        .use_api_token(&hash_token(token))
+        .await?
+        .ok_or(Error::BadOAuth)?;
+
+    if !api_token.allows(scope) {
+        return Err(Error::MissingScope(scope.as_str().to_string()));
+    }
+    Ok(Some(api_token))
+}
+
+/// The spotify user id of whoever the request is for
+pub async fn caller_user_id(
+    headers: &HeaderMap,
+    session: &Session,
+    app_state: &Arc<AppState>,
+    scope: ApiScope,
+) -> Result<String> {
+    match api_token(headers, app_state, scope).await? {
+        Some(api_token) => Ok(api_token.spotify_user_id),
+        None => {
+            let token = session_access_token(session, app_state.clone()).await?;
+            session_user_id(session, &token).await
+        }
+    }
+}
+
+/// A spotify access token of whoever the request is for
+pub async fn caller_access_token(
+    headers: &HeaderMap,
+    session: &Session,
+    app_state: &Arc<AppState>,
+    scope: ApiScope,
+) -> Result<String> {
+    match api_token(headers, app_state, scope).await? {
+        Some(api_token) => api_token_access_token(app_state, api_token).await,
+        None => session_access_token(session, app_state.clone()).await,
+    }
+}
+
+/// A spotify access token for the user of an API token, kept until it expires
+pub async fn api_token_access_token(
+    app_state: &Arc<AppState>,
+    api_token: DBApiToken,
+) -> Result<String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let cached = app_state
+        .api_token_cache
+        .lock()
+        .unwrap()
+        .get(&api_token.id)
+        .cloned();
+    if let Some((access_token, expire_time)) = cached {
+        if expire_time > now + EXPIRY_MARGIN {
+            return Ok(access_token);
+        }
+    }
+
+    let refresh_token = api_token.spotify_refresh_token.ok_or(Error::BadOAuth)?;
+    // API tokens are authorized through our callback with the client secret, never with PKCE
+    let token_info = request_refreshed_token(&refresh_token, false, app_state).await?;
+    if let Some(new_refresh_token) = &token_info.refresh_token {
+        app_state
+            .database
+            .set_api_token_refresh_token(api_token.id, new_refresh_token)
+            .await?;
+    }
+
+    app_state.api_token_cache.lock().unwrap().insert(
+        api_token.id,
+        (token_info.access_token.clone(), now + token_info.expires_in),
+    );
+    Ok(token_info.access_token)
+}
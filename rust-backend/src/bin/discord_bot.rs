@@ -0,0 +1,26 @@
+use std::{env, sync::Arc};
+
+use backend::{
+    AppState,
+    discord::{Bot, SpotifyLookup, gateway::TwilightGateway},
+};
+use dotenv::dotenv;
+use env_logger::Target;
+use log::info;
+
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+
+    env_logger::Builder::new()
+        .filter_level(log::LevelFilter::Info)
+        .filter_module("tracing", log::LevelFilter::Warn)
+        .target(Target::Stdout)
+        .init();
+
+    let app_state = Arc::new(AppState::load().await);
+    let gateway = TwilightGateway::new(env::var("DiscordToken").unwrap());
+
+    info!("Discord bot started");
+    Bot::new(gateway, SpotifyLookup::new(app_state)).run().await;
+}
@@ -0,0 +1,138 @@
+use super::Database;
+use crate::Result;
+use crate::types::{ApiScope, ApiToken};
+use sqlx::{FromRow, Postgres};
+
+/// Who an API token belongs to and what it may do
+#[derive(FromRow)]
+pub struct DBApiToken {
+    pub id: i32,
+    pub spotify_user_id: String,
+    pub scopes: Vec<String>,
+    pub spotify_refresh_token: Option<String>,
+}
+
+impl DBApiToken {
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.scopes.iter().any(|s| s == scope.as_str())
+    }
+}
+
+impl Database {
+    pub async fn create_api_token(
+        &self,
+        spotify_user_id: &String,
+        name: &String,
+        token_hash: &String,
+        scopes: &Vec<ApiScope>,
+    ) -> Result<ApiToken> {
+        let scopes: Vec<&str> = scopes.iter().map(|s| s.as_str()).collect();
+        Ok(sqlx::query_as::<Postgres, ApiToken>(
+            r#"
+                INSERT INTO api_tokens (spotify_user_id, name, token_hash, scopes)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id, name, scopes, created_at, last_used_at, revoked_at
+                "#,
+        )
+        .bind(spotify_user_id)
+        .bind(name)
+        .bind(token_hash)
+        .bind(scopes)
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Gives a token the spotify login that was made for it. False when the token was revoked
+    /// or belongs to someone other than who logged in to spotify
+    pub async fn set_api_token_grant(
+        &self,
+        token_id: i32,
+        spotify_user_id: &String,
+        spotify_refresh_token: &String,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+                UPDATE api_tokens SET spotify_refresh_token = $3
+                WHERE id = $1 AND spotify_user_id = $2 AND revoked_at IS NULL
+                "#,
+        )
+        .bind(token_id)
+        .bind(spotify_user_id)
+        .bind(spotify_refresh_token)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get_api_tokens(&self, spotify_user_id: &String) -> Result<Vec<ApiToken>> {
+        Ok(sqlx::query_as::<Postgres, ApiToken>(
+            r#"
+                SELECT id, name, scopes, created_at, last_used_at, revoked_at
+                FROM api_tokens
+                WHERE spotify_user_id = $1
+                ORDER BY created_at DESC
+                "#,
+        )
+        .bind(spotify_user_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// False when the user has no such token
+    pub async fn revoke_api_token(&self, spotify_user_id: &String, token_id: i32) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+                UPDATE api_tokens
+                SET revoked_at = COALESCE(revoked_at, NOW()), spotify_refresh_token = NULL
+                WHERE id = $1 AND spotify_user_id = $2
+                "#,
+        )
+        .bind(token_id)
+        .bind(spotify_user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revokes every token of a user, giving the ids that were still in use
+    pub async fn revoke_all_api_tokens(&self, spotify_user_id: &String) -> Result<Vec<i32>> {
+        Ok(sqlx::query_scalar::<Postgres, i32>(
+            r#"
+                UPDATE api_tokens SET revoked_at = NOW(), spotify_refresh_token = NULL
+                WHERE spotify_user_id = $1 AND revoked_at IS NULL
+                RETURNING id
+                "#,
+        )
+        .bind(spotify_user_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Looks up a token that has not been revoked and marks it as used
+    pub async fn use_api_token(&self, token_hash: &String) -> Result<Option<DBApiToken>> {
+        Ok(sqlx::query_as::<Postgres, DBApiToken>(
+            r#"
+                UPDATE api_tokens SET last_used_at = NOW()
+                WHERE token_hash = $1 AND revoked_at IS NULL
+                RETURNING id, spotify_user_id, scopes, spotify_refresh_token
+                "#,
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// Spotify sometimes hands out a new refresh token, the old one stops working then
+    pub async fn set_api_token_refresh_token(
+        &self,
+        token_id: i32,
+        spotify_refresh_token: &String,
+    ) -> Result<()> {
+        sqlx::query("UPDATE api_tokens SET spotify_refresh_token = $1 WHERE id = $2")
+            .bind(spotify_refresh_token)
+            .bind(token_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
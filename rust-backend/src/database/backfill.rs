@@ -0,0 +1,150 @@
+use super::Database;
+use super::databasetypes::DBAnime;
+use super::regex_search::artist_name_variants;
+use crate::Result;
+use crate::anilist::Media;
+use crate::anilist::types::AnilistID;
+use crate::japanese_processing::TextVariants;
+use log::info;
+use sqlx::Postgres;
+
+impl Database {
+    // AniList is asked 50 at a time, this only bounds how much one franchise query walks
+    const FRANCHISE_BACKFILL_CHUNK: usize = 500;
+
+    /// Recomputes the stored romaji and normalized name variants of every artist, anime song and song group,
+    /// and the search titles of every anime.
+    /// Run with `main backfill-names` after changing how variants are made.
+    pub async fn backfill_name_variants(&self) -> Result<()> {
+        let artists =
+            sqlx::query_as::<Postgres, (i32, Vec<String>)>("SELECT ann_id, names FROM new_artists")
+                .fetch_all(&self.pool)
+                .await?;
+
+        info!("Backfilling name variants for {} artists", artists.len());
+
+        let mut tx = self.pool.begin().await?;
+        for (ann_id, names) in &artists {
+            let variants = artist_name_variants(names);
+            let romaji: Vec<&String> = variants.iter().map(|v| &v.romaji).collect();
+            let normalized: Vec<&String> = variants.iter().map(|v| &v.normalized).collect();
+
+            sqlx::query(
+                "UPDATE new_artists SET romaji_names = $2, normalized_names = $3 WHERE ann_id = $1",
+            )
+            .bind(ann_id)
+            .bind(&romaji)
+            .bind(&normalized)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query("DELETE FROM artist_search_names WHERE ann_id = $1")
+                .bind(ann_id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query(
+                "INSERT INTO artist_search_names (ann_id, search_name)
+                SELECT $1, unnest($2::text[]) ON CONFLICT DO NOTHING",
+            )
+            .bind(ann_id)
+            .bind(&normalized)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        let songs =
+            sqlx::query_as::<Postgres, (i32, String)>("SELECT ann_song_id, song_name FROM animes")
+                .fetch_all(&self.pool)
+                .await?;
+
+        info!("Backfilling name variants for {} anime songs", songs.len());
+
+        let (ids, variants): (Vec<i32>, Vec<TextVariants>) = songs
+            .iter()
+            .map(|(id, name)| (*id, TextVariants::new(name)))
+            .unzip();
+
+        sqlx::query(
+            "UPDATE animes SET song_name_romaji = v.romaji, song_name_normalized = v.normalized
+            FROM unnest($1::int[], $2::text[], $3::text[]) AS v(ann_song_id, romaji, normalized)
+            WHERE animes.ann_song_id = v.ann_song_id",
+        )
+        .bind(&ids)
+        .bind(variants.iter().map(|v| &v.romaji).collect::<Vec<&String>>())
+        .bind(
+            variants
+                .iter()
+                .map(|v| &v.normalized)
+                .collect::<Vec<&String>>(),
+        )
+        .execute(&self.pool)
+        .await?;
+
+        let groups = sqlx::query_as::<Postgres, (i32, String)>(
+            "SELECT group_id, song_title FROM song_groups",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        info!("Backfilling name variants for {} song groups", groups.len());
+
+        let (ids, variants): (Vec<i32>, Vec<TextVariants>) = groups
+            .iter()
+            .map(|(id, title)| (*id, TextVariants::new(title)))
+            .unzip();
+
+        sqlx::query(
+            "UPDATE song_groups SET song_title_romaji = v.romaji, song_title_normalized = v.normalized
+            FROM unnest($1::int[], $2::text[], $3::text[]) AS v(group_id, romaji, normalized)
+            WHERE song_groups.group_id = v.group_id",
+        )
+        .bind(&ids)
+        .bind(variants.iter().map(|v| &v.romaji).collect::<Vec<&String>>())
+        .bind(variants.iter().map(|v| &v.normalized).collect::<Vec<&String>>())
+        .execute(&self.pool)
+        .await?;
+
+        let animes = sqlx::query_as::<Postgres, DBAnime>(
+            "SELECT DISTINCT ON (ann_id) * FROM animes ORDER BY ann_id, last_updated DESC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        info!("Backfilling search titles for {} animes", animes.len());
+
+        sqlx::query("DELETE FROM anime_title_search")
+            .execute(&self.pool)
+            .await?;
+        for chunk in animes.chunks(1000) {
+            self.add_anime_search_titles(&chunk.iter().collect())
+                .await?;
+        }
+
+        info!("Backfill done");
+        Ok(())
+    }
+
+    /// Fetches the AniList relations of every anime that has no franchise yet and groups them into franchises.
+    /// Run with `main backfill-franchises`, animes saved before franchises existed have none.
+    /// Animes that are done get a franchise, so an interrupted backfill can just be run again
+    pub async fn backfill_franchises(&self) -> Result<()> {
+        let anilist_ids = sqlx::query_scalar::<Postgres, i32>(
+            "SELECT DISTINCT anilist_id FROM animes WHERE anilist_id IS NOT NULL AND franchise_id IS NULL",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        info!("Backfilling franchises for {} animes", anilist_ids.len());
+
+        for chunk in anilist_ids.chunks(Self::FRANCHISE_BACKFILL_CHUNK) {
+            let media = Media::fetch_many(chunk.iter().map(|id| AnilistID(*id)).collect()).await?;
+            self.save_franchise_relations(&media).await?;
+            info!("Backfilled franchises for {} animes", chunk.len());
+        }
+
+        info!("Backfill done");
+        Ok(())
+    }
+}
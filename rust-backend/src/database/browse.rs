@@ -0,0 +1,221 @@
+use super::Database;
+use super::databasetypes::{DBAnime, DBArtist, SongGroupLink};
+use crate::anisong::Anime;
+use crate::types::{AnimeSong, ArtistDiscography, ArtistSong, ArtistSummary, FrontendAnimeEntry};
+use crate::{Error, Result};
+use sqlx::Postgres;
+use std::collections::HashMap;
+
+impl Database {
+    pub async fn get_animes_by_ann_id(&self, ann_id: i32) -> Result<Vec<DBAnime>> {
+        Ok(
+            sqlx::query_as::<Postgres, DBAnime>("SELECT * FROM animes WHERE ann_id = $1")
+                .bind(ann_id)
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    pub async fn get_animes_by_ann_song_ids(
+        &self,
+        ann_song_ids: &Vec<i32>,
+    ) -> Result<Vec<DBAnime>> {
+        Ok(
+            sqlx::query_as::<Postgres, DBAnime>("SELECT * FROM animes WHERE ann_song_id = ANY($1)")
+                .bind(ann_song_ids)
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    pub async fn get_animes_by_mal_id(&self, mal_id: i32) -> Result<Vec<DBAnime>> {
+        Ok(
+            sqlx::query_as::<Postgres, DBAnime>("SELECT * FROM animes WHERE mal_id = $1")
+                .bind(mal_id)
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    /// All spotify tracks bound to each of the given song groups
+    pub async fn get_spotify_ids_by_group_ids(
+        &self,
+        group_ids: Vec<i32>,
+    ) -> Result<HashMap<i32, Vec<String>>> {
+        let links = sqlx::query_as::<Postgres, SongGroupLink>(
+            "SELECT * FROM song_group_links WHERE group_id = ANY($1)",
+        )
+        .bind(&group_ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut spotify_ids: HashMap<i32, Vec<String>> = HashMap::with_capacity(links.len());
+        for link in links {
+            spotify_ids
+                .entry(link.group_id)
+                .or_default()
+                .push(link.spotify_id);
+        }
+        Ok(spotify_ids)
+    }
+
+    /// Merges what we have in our database with what anisong knows and annotates every song
+    /// with its bound spotify tracks, ordered by anime index and then track index.
+    pub async fn get_anime_songs(
+        &self,
+        db_animes: Vec<DBAnime>,
+        anisong_animes: Vec<Anime>,
+    ) -> Result<Vec<AnimeSong>> {
+        let (_, mut animes) = self
+            .merge(vec![], db_animes, vec![], anisong_animes, None)
+            .await?;
+
+        animes.sort_by(|a, b| {
+            a.index_type
+                .cmp(&b.index_type)
+                .then(a.index_number.total_cmp(&b.index_number))
+                .then(a.track_index_type.cmp(&b.track_index_type))
+                .then(a.track_index_number.cmp(&b.track_index_number))
+        });
+
+        let spotify_ids = self
+            .get_spotify_ids_by_group_ids(animes.iter().filter_map(|a| a.song_group_id).collect())
+            .await?;
+
+        Ok(animes
+            .iter()
+            .map(|a| AnimeSong {
+                anime_info: FrontendAnimeEntry::from_db_anime(a),
+                spotify_ids: a
+                    .song_group_id
+                    .and_then(|id| spotify_ids.get(&id).cloned())
+                    .unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    pub async fn get_artists_by_ann_ids(&self, ann_ids: &Vec<i32>) -> Result<Vec<DBArtist>> {
+        Ok(
+            sqlx::query_as::<Postgres, DBArtist>(
+                "SELECT * FROM new_artists WHERE ann_id = ANY($1)",
+            )
+            .bind(ann_ids)
+            .fetch_all(&self.pool)
+            .await?,
+        )
+    }
+
+    pub async fn get_artist_links(&self, ann_id: i32) -> Result<Vec<String>> {
+        Ok(sqlx::query_scalar::<Postgres, String>(
+            "SELECT spotify_id FROM artist_links WHERE ann_id = $1",
+        )
+        .bind(ann_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Songs performed by the artist or by any group the artist is part of
+    pub async fn get_animes_by_performer(&self, artist: &DBArtist) -> Result<Vec<DBAnime>> {
+        let mut ann_ids = vec![artist.ann_id];
+        ann_ids.extend(artist.groups_ids.iter().flatten());
+
+        Ok(
+            sqlx::query_as::<Postgres, DBAnime>("SELECT * FROM animes WHERE artists_ann_id && $1")
+                .bind(&ann_ids)
+                .fetch_all(&self.pool)
+                .await?,
+        )
+    }
+
+    /// Everything we know about an artist, with their anime songs grouped by song_group
+    pub async fn get_artist_discography(
+        &self,
+        ann_id: i32,
+        anisong_animes: Vec<Anime>,
+    ) -> Result<ArtistDiscography> {
+        // Artists anisong knows but we never stored are built from the anisong credits
+        let artist = match self
+            .get_artists_by_ann_ids(&vec![ann_id])
+            .await?
+            .into_iter()
+            .next()
+        {
+            Some(artist) => artist,
+            None => DBArtist::find_in_anisongs(ann_id, &anisong_animes).ok_or(Error::NotFound)?,
+        };
+
+        let related_ids: Vec<i32> = artist
+            .groups_ids
+            .iter()
+            .chain(artist.members.iter())
+            .flatten()
+            .cloned()
+            .collect();
+        let related: HashMap<i32, Vec<String>> = self
+            .get_artists_by_ann_ids(&related_ids)
+            .await?
+            .into_iter()
+            .map(|a| (a.ann_id, a.names))
+            .collect();
+        let summarize = |ids: &Option<Vec<i32>>| -> Vec<ArtistSummary> {
+            ids.iter()
+                .flatten()
+                .map(|id| ArtistSummary {
+                    ann_id: *id,
+                    names: related.get(id).cloned().unwrap_or_default(),
+                })
+                .collect()
+        };
+
+        let db_animes = self.get_animes_by_performer(&artist).await?;
+        let (_, mut animes) = self
+            .merge(vec![], db_animes, vec![], anisong_animes, None)
+            .await?;
+        animes.sort_by(|a, b| {
+            a.song_name
+                .cmp(&b.song_name)
+                .then(a.title_eng.cmp(&b.title_eng))
+        });
+
+        let spotify_ids = self
+            .get_spotify_ids_by_group_ids(animes.iter().filter_map(|a| a.song_group_id).collect())
+            .await?;
+
+        // Songs without a song group yet are grouped the same way song_groups are made
+        let mut songs: Vec<ArtistSong> = Vec::new();
+        for anime in &animes {
+            let existing = songs.iter_mut().find(|s| match anime.song_group_id {
+                Some(id) => s.song_group_id == Some(id),
+                None => {
+                    s.song_group_id.is_none()
+                        && s.song_name == anime.song_name
+                        && s.artist_ids == anime.artists_ann_id
+                }
+            });
+            match existing {
+                Some(song) => song.animes.push(FrontendAnimeEntry::from_db_anime(anime)),
+                None => songs.push(ArtistSong {
+                    song_group_id: anime.song_group_id,
+                    song_name: anime.song_name.clone(),
+                    artist_ids: anime.artists_ann_id.clone(),
+                    artist_names: anime.artist_names.clone(),
+                    spotify_ids: anime
+                        .song_group_id
+                        .and_then(|id| spotify_ids.get(&id).cloned())
+                        .unwrap_or_default(),
+                    animes: vec![FrontendAnimeEntry::from_db_anime(anime)],
+                }),
+            }
+        }
+
+        Ok(ArtistDiscography {
+            ann_id: artist.ann_id,
+            groups: summarize(&artist.groups_ids),
+            members: summarize(&artist.members),
+            spotify_ids: self.get_artist_links(artist.ann_id).await?,
+            spotify_artists: self.get_linked_spotify_artists(artist.ann_id).await?,
+            names: artist.names,
+            songs,
+        })
+    }
+}
@@ -1,12 +1,18 @@
 use crate::anilist::Media;
-use crate::anisong::Anime;
+use crate::anisong::{Anime, Artist};
 // use axum_sessions::async_session::chrono::{DateTime, Utc};
+use super::regex_search::process_artist_name;
 use crate::Result;
-use crate::japanese_processing::process_similarity;
+use crate::japanese_processing::{
+    TextVariants, normalize_text, normalized_similarity, search_form,
+};
 use crate::types::{AnimeIndex, AnimeTrackIndex, AnimeType};
 use axum_sessions::async_session::chrono::{DateTime, Duration, Utc};
+use fuzzywuzzy::fuzz;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::collections::{HashMap, HashSet};
 
 use crate::anilist::types::{AnilistID, HexColor, ImageURL, TagID, URL};
 
@@ -15,6 +21,8 @@ pub struct DBAnime {
     pub ann_id: i32,
     pub title_eng: String,
     pub title_jpn: String,
+    pub title_romaji: Option<String>,
+    pub title_native: Option<String>,
     pub index_type: i16,
     pub index_number: f32,
     pub anime_type: i16,
@@ -47,6 +55,8 @@ pub struct DBAnime {
     // Song info
     pub ann_song_id: i32,
     pub song_name: String,
+    pub song_name_romaji: Option<String>,
+    pub song_name_normalized: Option<String>,
     pub spotify_artist_ids: Option<Vec<String>>,
     // pub spotify_title: String, // ?
     pub artist_names: Vec<String>,
@@ -56,6 +66,17 @@ pub struct DBAnime {
 
     pub track_index_type: i16,
     pub track_index_number: i32,
+
+    // Song details from anisong
+    pub hq_url: Option<String>,
+    pub mq_url: Option<String>,
+    pub audio_url: Option<String>,
+    pub song_difficulty: Option<f64>,
+    pub song_length: Option<f64>,
+    pub song_category: Option<String>,
+    pub is_dub: Option<bool>,
+    pub is_rebroadcast: Option<bool>,
+    pub alt_titles: Option<Vec<String>>,
     // linked_ids
     pub mal_id: Option<i32>,
     pub anilist_id: Option<AnilistID>,
@@ -63,6 +84,7 @@ pub struct DBAnime {
     pub kitsu_id: Option<i32>,
 
     pub song_group_id: Option<i32>,
+    pub franchise_id: Option<i32>,
     // pub date_added: Option<DateTime<Utc>>,
     pub last_updated: DateTime<Utc>,
 }
@@ -76,10 +98,17 @@ impl DBAnime {
             return Ok((vec![], 0.0));
         }
 
-        // Compute similarity scores
+        let song_name = search_form(song_name);
+
+        // Compute similarity scores, against the stored normalized title when we have one
         let evaluated_animes: Vec<f32> = animes
             .iter()
-            .map(|a| process_similarity(song_name, &a.song_name))
+            .map(|a| match &a.song_name_normalized {
+                Some(normalized) => normalized_similarity(&song_name, normalized),
+                None => {
+                    normalized_similarity(&song_name, &TextVariants::new(&a.song_name).normalized)
+                }
+            })
             .collect();
 
         // Find the max score
@@ -102,6 +131,51 @@ impl DBAnime {
         Ok((best_animes, max_score))
     }
 
+    pub fn pick_best_by_artist_names(
+        animes: &mut Vec<DBAnime>,
+        artist_names: Vec<&String>,
+    ) -> Result<(Vec<DBAnime>, f32)> {
+        if animes.is_empty() {
+            return Ok((vec![], 0.0));
+        }
+
+        let artist_names = search_form(
+            &artist_names
+                .into_iter()
+                .map(|a| process_artist_name(&a))
+                .join(" "),
+        );
+
+        let evaluated_animes: Vec<f32> = animes
+            .iter()
+            .map(|a| {
+                fuzz::token_set_ratio(
+                    &artist_names,
+                    &normalize_text(&a.artist_names.join(" ")),
+                    true,
+                    true,
+                ) as f32
+            })
+            .collect();
+
+        let max_score = evaluated_animes
+            .iter()
+            .map(|score| *score)
+            .fold(f32::MIN, f32::max);
+
+        let mut best_animes = Vec::new();
+        let mut i = evaluated_animes.len();
+
+        while i > 0 {
+            i -= 1;
+            if evaluated_animes[i] == max_score {
+                best_animes.push(animes.swap_remove(i));
+            }
+        }
+
+        Ok((best_animes, max_score))
+    }
+
     pub fn from_anisong_and_anilist(
         anisong: &Anime,
         anilist: Option<&Media>,
@@ -117,10 +191,13 @@ impl DBAnime {
         let tags = anilist.map(|a| a.tags.as_ref()).flatten();
         let trailer = anilist.map(|a| a.trailer.as_ref()).flatten();
         let track_index = AnimeTrackIndex::from_str(&anisong.songType).unwrap();
+        let song_name_variants = TextVariants::new(&anisong.songName);
         Self {
             ann_id: anisong.annId,
             title_eng: anisong.animeENName.clone(),
             title_jpn: anisong.animeJPName.clone(),
+            title_romaji: anilist.map(|a| a.title.romaji.clone()).flatten(),
+            title_native: anilist.map(|a| a.title.native.clone()).flatten(),
             index_type: anime_index.discriminant() as i16,
             index_number: anime_index.value(),
             anime_type: anime_type as i16,
@@ -152,6 +229,8 @@ impl DBAnime {
                 .flatten(),
             ann_song_id: anisong.annSongId,
             song_name: anisong.songName.clone(),
+            song_name_romaji: Some(song_name_variants.romaji),
+            song_name_normalized: Some(song_name_variants.normalized),
             spotify_artist_ids: /*spotify_artist_ids*/ Some(vec![]),
             artist_names: anisong.artists.iter().map(|a| a.names[0].clone()).collect(),
             artists_ann_id: anisong.artists.iter().map(|a| a.id).collect(),
@@ -159,11 +238,21 @@ impl DBAnime {
             arrangers_ann_id: anisong.arrangers.iter().map(|a| a.id).collect(),
             track_index_type: track_index.discriminant() as i16,
             track_index_number: track_index.value(),
+            hq_url: anisong.HQ.clone(),
+            mq_url: anisong.MQ.clone(),
+            audio_url: anisong.audio.clone(),
+            song_difficulty: anisong.songDifficulty,
+            song_length: anisong.songLength,
+            song_category: Some(anisong.songCategory.clone()),
+            is_dub: Some(anisong.isDub),
+            is_rebroadcast: Some(anisong.isRebroadcast),
+            alt_titles: anisong.animeAltName.clone(),
             mal_id: anisong.linked_ids.myanimelist,
             anilist_id: anisong.linked_ids.anilist,
             anidb_id: anisong.linked_ids.anidb,
             kitsu_id: anisong.linked_ids.kitsu,
             song_group_id: group_id,
+            franchise_id: None,
             last_updated: Utc::now(),
         }
     }
@@ -173,6 +262,8 @@ impl DBAnime {
         let studios = anilist_data.studios.as_ref().map(|s| &s.nodes);
         let tags = anilist_data.tags.as_ref();
         let trailer = anilist_data.trailer.as_ref();
+        self.title_romaji = anilist_data.title.romaji.clone();
+        self.title_native = anilist_data.title.native.clone();
         self.mean_score = Some(anilist_data.mean_score);
         self.banner_image = anilist_data.banner_image.clone();
         self.cover_image_color = cover_image.map(|b| b.color.clone()).flatten();
@@ -198,11 +289,58 @@ impl DBAnime {
         self.last_updated = Utc::now();
     }
 
+    /// Every title the anime goes by, english and japanese first
+    pub fn titles(&self) -> Vec<&String> {
+        let mut titles = vec![&self.title_eng, &self.title_jpn];
+        titles.extend(self.title_romaji.iter());
+        titles.extend(self.title_native.iter());
+        titles.extend(self.alt_titles.iter().flatten());
+        titles.into_iter().unique().collect()
+    }
+
+    /// Takes the song details from anisong, returns whether anything changed
+    pub fn update_from_anisong(&mut self, anisong: &Anime) -> bool {
+        let song_category = Some(anisong.songCategory.clone());
+        let changed = self.hq_url != anisong.HQ
+            || self.mq_url != anisong.MQ
+            || self.audio_url != anisong.audio
+            || self.song_difficulty != anisong.songDifficulty
+            || self.song_length != anisong.songLength
+            || self.song_category != song_category
+            || self.is_dub != Some(anisong.isDub)
+            || self.is_rebroadcast != Some(anisong.isRebroadcast)
+            || self.alt_titles != anisong.animeAltName;
+
+        if changed {
+            self.hq_url = anisong.HQ.clone();
+            self.mq_url = anisong.MQ.clone();
+            self.audio_url = anisong.audio.clone();
+            self.song_difficulty = anisong.songDifficulty;
+            self.song_length = anisong.songLength;
+            self.song_category = song_category;
+            self.is_dub = Some(anisong.isDub);
+            self.is_rebroadcast = Some(anisong.isRebroadcast);
+            self.alt_titles = anisong.animeAltName.clone();
+        }
+        changed
+    }
+
+    /// Updates the animes with new anilist data and whatever anisong returned for them (by ann_song_id)
     pub fn update_all(
         db_animes: &mut Vec<DBAnime>,
         new_anilist: &Vec<Media>,
+        anisongs: &HashMap<i32, Anime>,
         group_id: Option<i32>,
     ) -> Vec<DBAnime> {
+        let mut anisong_updated = HashSet::new();
+        for db_anime in db_animes.iter_mut() {
+            if let Some(anisong) = anisongs.get(&db_anime.ann_song_id) {
+                if db_anime.update_from_anisong(anisong) {
+                    anisong_updated.insert(db_anime.ann_song_id);
+                }
+            }
+        }
+
         db_animes.sort_by(|a, b| {
             a.anilist_id
                 .unwrap_or(AnilistID(-1))
@@ -234,13 +372,22 @@ impl DBAnime {
             };
         }
         if group_id.is_some() {
-            for dbanime in db_animes {
+            for dbanime in db_animes.iter_mut() {
                 if dbanime.song_group_id.is_none() {
                     dbanime.song_group_id = group_id;
                     updated_anime.push(dbanime.clone());
                 }
             }
         }
+        for updated in &updated_anime {
+            anisong_updated.remove(&updated.ann_song_id);
+        }
+        updated_anime.extend(
+            db_animes
+                .iter()
+                .filter(|a| anisong_updated.contains(&a.ann_song_id))
+                .cloned(),
+        );
         updated_anime
     }
 
@@ -310,6 +457,46 @@ pub struct DBArtist {
     pub names: Vec<String>,
     pub groups_ids: Option<Vec<i32>>,
     pub members: Option<Vec<i32>>,
+    pub romaji_names: Option<Vec<String>>,
+    pub normalized_names: Option<Vec<String>>,
+}
+
+impl DBArtist {
+    /// The artist as anisong credits it on a song, for artists we haven't stored yet
+    pub fn from_anisong(artist: &Artist) -> Self {
+        Self {
+            ann_id: artist.id,
+            names: artist.names.clone(),
+            groups_ids: artist
+                .groups
+                .as_ref()
+                .map(|groups| groups.iter().map(|a| a.id).collect()),
+            members: artist
+                .members
+                .as_ref()
+                .map(|members| members.iter().map(|a| a.id).collect()),
+            romaji_names: None,
+            normalized_names: None,
+        }
+    }
+
+    /// Finds an artist among the performers of some anisong songs, also looking inside groups and members
+    pub fn find_in_anisongs(ann_id: i32, animes: &[Anime]) -> Option<Self> {
+        fn find(ann_id: i32, artists: &[Artist]) -> Option<&Artist> {
+            artists.iter().find_map(|artist| {
+                if artist.id == ann_id {
+                    return Some(artist);
+                }
+                find(ann_id, artist.groups.as_deref().unwrap_or_default())
+                    .or_else(|| find(ann_id, artist.members.as_deref().unwrap_or_default()))
+            })
+        }
+
+        animes
+            .iter()
+            .find_map(|anime| find(ann_id, &anime.artists))
+            .map(Self::from_anisong)
+    }
 }
 
 #[derive(FromRow, Serialize, Deserialize)]
@@ -317,6 +504,8 @@ pub struct SongGroup {
     pub group_id: i32,
     pub song_title: String,
     pub artist_ids: Vec<i32>,
+    pub song_title_romaji: Option<String>,
+    pub song_title_normalized: Option<String>,
 }
 
 #[derive(FromRow, Serialize, Deserialize)]
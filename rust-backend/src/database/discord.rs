@@ -0,0 +1,46 @@
+use super::Database;
+use super::api_tokens::DBApiToken;
+use crate::Result;
+use sqlx::Postgres;
+
+impl Database {
+    pub async fn link_discord_user(&self, discord_user_id: i64, api_token_id: i32) -> Result<()> {
+        sqlx::query(
+            r#"
+                INSERT INTO discord_links (discord_user_id, api_token_id)
+                VALUES ($1, $2)
+                ON CONFLICT (discord_user_id)
+                DO UPDATE SET api_token_id = EXCLUDED.api_token_id, linked_at = NOW()
+                "#,
+        )
+        .bind(discord_user_id)
+        .bind(api_token_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// False when the discord user was not linked
+    pub async fn unlink_discord_user(&self, discord_user_id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM discord_links WHERE discord_user_id = $1")
+            .bind(discord_user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The API token a discord user linked, None once it is revoked
+    pub async fn get_discord_link(&self, discord_user_id: i64) -> Result<Option<DBApiToken>> {
+        Ok(sqlx::query_as::<Postgres, DBApiToken>(
+            r#"
+                SELECT tokens.id, tokens.spotify_user_id, tokens.scopes, tokens.spotify_refresh_token
+                FROM discord_links AS links
+                JOIN api_tokens AS tokens ON tokens.id = links.api_token_id
+                WHERE links.discord_user_id = $1 AND tokens.revoked_at IS NULL
+                "#,
+        )
+        .bind(discord_user_id)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+}
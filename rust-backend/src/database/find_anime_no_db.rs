@@ -14,7 +14,7 @@ impl Database {
     ) -> Result<NewSong> {
         let romanji_title = process_possible_japanese(&song.name);
 
-        let mut anime = anisong_db.find_songs_by_artists(&song).await.unwrap();
+        let mut anime = anisong_db.find_songs_by_artists(&song).await?;
 
         let mut found_by_artist = true;
 
@@ -23,19 +23,24 @@ impl Database {
 
             anime = anisong_db
                 .get_animes_by_song_title(romanji_title.clone(), false)
-                .await
-                .unwrap();
+                .await?;
         }
 
         if !anime.is_empty() {
             let (best_anime, max_score) = if found_by_artist {
-                AnisongClient::pick_best_by_song_name(&mut anime, &song.name).unwrap()
+                AnisongClient::pick_best_by_song_name(&mut anime, &song.name)?
             } else {
-                AnisongClient::pick_best_by_artist_names(
-                    &mut anime,
-                    song.artists.iter().map(|a| &a.name).collect(),
-                )
-                .unwrap()
+                let artist_names: Vec<&String> = song.artists.iter().map(|a| &a.name).collect();
+                let (mut best_anime, max_score) =
+                    AnisongClient::pick_best_by_artist_names(&mut anime, artist_names.clone())?;
+
+                // The performers did not match, spotify might be crediting the composer or arranger
+                if max_score > accuracy_cutoff {
+                    (best_anime, max_score)
+                } else {
+                    anime.append(&mut best_anime);
+                    AnisongClient::pick_best_by_credited_names(&mut anime, artist_names)?
+                }
             };
 
             let mut song_group_id = None;
@@ -46,17 +51,16 @@ impl Database {
                         &best_anime[0].songName,
                         &best_anime[0].artists.iter().map(|a| a.id).collect(),
                     )
-                    .await,
+                    .await?,
                 );
 
                 self.try_add_artists(&best_anime[0].artists, &song.artists)
-                    .await;
+                    .await?;
             }
 
             let (mut hit, mut more) = self
                 .merge(vec![], vec![], best_anime, anime, song_group_id)
-                .await
-                .unwrap();
+                .await?;
 
             if max_score > accuracy_cutoff {
                 return Ok(types::NewSong::Hit(SongHit {
@@ -64,6 +68,7 @@ impl Database {
                     certainty: max_score as i32,
                     anime_info: FrontendAnimeEntry::from_db_animes(&hit),
                     more_with_artist: FrontendAnimeEntry::from_db_animes(&more),
+                    franchises: None,
                 }));
             } else {
                 more.append(&mut hit);
@@ -76,13 +81,11 @@ impl Database {
         } else {
             let possible_anime = anisong_db
                 .get_animes_by_song_title(romanji_title.clone(), true)
-                .await
-                .unwrap();
+                .await?;
 
             let found_anime =
                 FrontendAnimeEntry::from_anisongs(&possible_anime.iter().map(|a| a).collect())
-                    .await
-                    .unwrap();
+                    .await?;
 
             let miss = SongMiss {
                 song_info: SongInfo::from_track_obj(song),
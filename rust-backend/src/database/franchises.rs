@@ -0,0 +1,81 @@
+use super::Database;
+use crate::Result;
+use crate::anilist::Media;
+use sqlx::{Postgres, QueryBuilder};
+use std::collections::HashMap;
+
+impl Database {
+    /// Saves the franchise relations of the media and recomputes the franchise of every anime connected to them,
+    /// returns the franchise id of each of the media by AniList id
+    pub async fn save_franchise_relations(&self, media: &Vec<Media>) -> Result<HashMap<i32, i32>> {
+        let relations: Vec<(i32, i32)> = media
+            .iter()
+            .flat_map(|m| {
+                let id = m.id.0;
+                m.franchise_relations()
+                    .into_iter()
+                    .map(move |related| (id, related))
+            })
+            .collect();
+
+        if !relations.is_empty() {
+            let mut query_builder: QueryBuilder<Postgres> =
+                QueryBuilder::new("INSERT INTO anime_relations (anilist_id, related_id) ");
+            query_builder.push_values(relations, |mut builder, (anilist_id, related_id)| {
+                builder.push_bind(anilist_id).push_bind(related_id);
+            });
+            query_builder.push(" ON CONFLICT DO NOTHING");
+            query_builder.build().execute(&self.pool).await?;
+        }
+
+        // Relations are followed both ways since AniList doesn't always link back.
+        // Every media is walked from in the same query, rows are (media it was reached from, anime)
+        let reached = sqlx::query_as::<Postgres, (i32, i32)>(
+            r#"
+                WITH RECURSIVE component(root, id) AS (
+                    SELECT seed, seed FROM unnest($1::int4[]) AS seed
+                    UNION
+                    SELECT component.root, CASE
+                        WHEN relations.anilist_id = component.id THEN relations.related_id
+                        ELSE relations.anilist_id
+                    END
+                    FROM anime_relations AS relations
+                    JOIN component
+                        ON relations.anilist_id = component.id OR relations.related_id = component.id
+                )
+                SELECT root, id FROM component
+                "#,
+        )
+        .bind(media.iter().map(|m| m.id.0).collect::<Vec<i32>>())
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut components: HashMap<i32, Vec<i32>> = HashMap::with_capacity(media.len());
+        for (root, id) in reached {
+            components.entry(root).or_default().push(id);
+        }
+
+        // Media in the same franchise reach the same animes, so they agree on the smallest id
+        let mut franchises = HashMap::new();
+        for component in components.values() {
+            let franchise_id = *component.iter().min().unwrap();
+            for member in component {
+                franchises.insert(*member, franchise_id);
+            }
+        }
+
+        let (anilist_ids, franchise_ids): (Vec<i32>, Vec<i32>) = franchises.iter().unzip();
+        sqlx::query(
+            "UPDATE animes SET franchise_id = v.franchise_id
+            FROM unnest($1::int4[], $2::int4[]) AS v(anilist_id, franchise_id)
+            WHERE animes.anilist_id = v.anilist_id
+                AND animes.franchise_id IS DISTINCT FROM v.franchise_id",
+        )
+        .bind(&anilist_ids)
+        .bind(&franchise_ids)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(franchises)
+    }
+}
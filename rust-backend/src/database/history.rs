@@ -0,0 +1,146 @@
+use super::Database;
+use super::databasetypes::DBAnime;
+use crate::Result;
+use crate::types::{FrontendAnimeEntry, HistoryEntry, HistoryPage, TrackType};
+use axum_sessions::async_session::chrono::{DateTime, Utc};
+use sqlx::{FromRow, Postgres};
+use std::collections::HashMap;
+
+#[derive(FromRow)]
+struct DBListen {
+    spotify_id: String,
+    ann_song_ids: Vec<i32>,
+    certainty: i32,
+    listened_at: DateTime<Utc>,
+}
+
+// Shared by the page and its count, $1 is the user and $2 to $5 the HistoryFilter
+const HISTORY_FILTER: &str = r#"
+    WHERE spotify_user_id = $1
+        AND ($2::timestamptz IS NULL OR listened_at >= $2)
+        AND ($3::timestamptz IS NULL OR listened_at < $3)
+        AND (
+            ($4::int4 IS NULL AND $5::int2 IS NULL)
+            OR EXISTS (
+                SELECT 1 FROM animes
+                WHERE animes.ann_song_id = ANY(history.ann_song_ids)
+                    AND ($4 IS NULL OR animes.ann_id = $4)
+                    AND ($5 IS NULL OR animes.track_index_type = $5)
+            )
+        )
+"#;
+
+/// What to narrow a users listening history down to, None means no filter
+pub struct HistoryFilter {
+    pub ann_id: Option<i32>,
+    pub track_type: Option<TrackType>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl Database {
+    pub async fn add_listen(
+        &self,
+        spotify_user_id: &String,
+        spotify_id: &String,
+        ann_song_ids: &Vec<i32>,
+        certainty: i32,
+    ) -> Result<()> {
+        // Refreshing the same song shouldn't count as listening to it again
+        sqlx::query(
+            r#"
+                INSERT INTO listen_history (spotify_user_id, spotify_id, ann_song_ids, certainty)
+                SELECT $1, $2, $3, $4
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM (
+                        SELECT spotify_id FROM listen_history
+                        WHERE spotify_user_id = $1
+                        ORDER BY listened_at DESC
+                        LIMIT 1
+                    ) AS last_listen
+                    WHERE last_listen.spotify_id = $2
+                )
+                "#,
+        )
+        .bind(spotify_user_id)
+        .bind(spotify_id)
+        .bind(ann_song_ids)
+        .bind(certainty)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// One page of a users listening history, newest first
+    pub async fn get_listen_history(
+        &self,
+        spotify_user_id: &String,
+        filter: &HistoryFilter,
+        page: i64,
+        per_page: i64,
+    ) -> Result<HistoryPage> {
+        let total = sqlx::query_scalar::<Postgres, i64>(&format!(
+            "SELECT COUNT(*) FROM listen_history AS history {}",
+            HISTORY_FILTER
+        ))
+        .bind(spotify_user_id)
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(filter.ann_id)
+        .bind(filter.track_type.map(|t| t.discriminant()))
+        .fetch_one(&self.pool)
+        .await?;
+
+        let listens = sqlx::query_as::<Postgres, DBListen>(&format!(
+            r#"
+                SELECT spotify_id, ann_song_ids, certainty, listened_at
+                FROM listen_history AS history
+                {}
+                ORDER BY listened_at DESC
+                LIMIT $6 OFFSET $7
+                "#,
+            HISTORY_FILTER
+        ))
+        .bind(spotify_user_id)
+        .bind(filter.from)
+        .bind(filter.to)
+        .bind(filter.ann_id)
+        .bind(filter.track_type.map(|t| t.discriminant()))
+        .bind(per_page)
+        // Far off pages are just empty, not an overflow
+        .bind(page.saturating_mul(per_page))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let ann_song_ids: Vec<i32> = listens
+            .iter()
+            .flat_map(|l| l.ann_song_ids.iter().cloned())
+            .collect();
+
+        let animes: HashMap<i32, DBAnime> = self
+            .get_animes_by_ann_song_ids(&ann_song_ids)
+            .await?
+            .into_iter()
+            .map(|a| (a.ann_song_id, a))
+            .collect();
+
+        Ok(HistoryPage {
+            total,
+            page,
+            entries: listens
+                .into_iter()
+                .map(|listen| HistoryEntry {
+                    anime_info: listen
+                        .ann_song_ids
+                        .iter()
+                        .filter_map(|id| animes.get(id))
+                        .map(FrontendAnimeEntry::from_db_anime)
+                        .collect(),
+                    spotify_id: listen.spotify_id,
+                    certainty: listen.certainty,
+                    listened_at: listen.listened_at,
+                })
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,177 @@
+use super::Database;
+use super::databasetypes::DBAnime;
+use crate::types::{FrontendAnimeEntry, ImportJob, ImportSource, LibraryTrack};
+use crate::{Error, Result};
+use axum_sessions::async_session::chrono::{DateTime, Utc};
+use sqlx::{FromRow, Postgres};
+use std::collections::HashMap;
+
+#[derive(FromRow)]
+struct DBLibraryTrack {
+    spotify_id: String,
+    source: ImportSource,
+    ann_song_ids: Vec<i32>,
+    certainty: i32,
+    imported_at: DateTime<Utc>,
+}
+
+impl Database {
+    pub async fn create_import_job(
+        &self,
+        spotify_user_id: &String,
+        source: ImportSource,
+        total: i32,
+    ) -> Result<i32> {
+        // A user has at most one running import, the partial unique index makes this race free
+        sqlx::query_scalar::<Postgres, i32>(
+            r#"
+                INSERT INTO import_jobs (spotify_user_id, source, total)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (spotify_user_id) WHERE finished_at IS NULL DO NOTHING
+                RETURNING id
+                "#,
+        )
+        .bind(spotify_user_id)
+        .bind(source)
+        .bind(total)
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::Conflict("An import is already running".to_string()))
+    }
+
+    /// Saves the outcome of one imported track and counts it towards the progress of its job,
+    /// a `None` certainty means the track isn't an anime song
+    pub async fn save_import_result(
+        &self,
+        job_id: i32,
+        spotify_user_id: &String,
+        source: ImportSource,
+        spotify_id: &String,
+        ann_song_ids: &Vec<i32>,
+        certainty: Option<i32>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+                INSERT INTO library_tracks (spotify_user_id, source, spotify_id, ann_song_ids, certainty)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (spotify_user_id, source, spotify_id) DO UPDATE SET
+                    ann_song_ids = EXCLUDED.ann_song_ids,
+                    certainty = EXCLUDED.certainty,
+                    imported_at = NOW()
+                "#,
+        )
+        .bind(spotify_user_id)
+        .bind(source)
+        .bind(spotify_id)
+        .bind(ann_song_ids)
+        .bind(certainty)
+        .execute(&self.pool)
+        .await?;
+
+        self.count_import_progress(job_id, certainty.is_some())
+            .await
+    }
+
+    pub async fn count_import_progress(&self, job_id: i32, hit: bool) -> Result<()> {
+        sqlx::query(
+            "UPDATE import_jobs SET processed = processed + 1, hits = hits + $2::int4 WHERE id = $1",
+        )
+        .bind(job_id)
+        .bind(hit as i32)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn finish_import_job(&self, job_id: i32, error: Option<&str>) -> Result<()> {
+        sqlx::query("UPDATE import_jobs SET finished_at = NOW(), error = $2 WHERE id = $1")
+            .bind(job_id)
+            .bind(error)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn has_running_import(&self, spotify_user_id: &String) -> Result<bool> {
+        Ok(sqlx::query_scalar::<Postgres, bool>(
+            "SELECT EXISTS (SELECT 1 FROM import_jobs WHERE spotify_user_id = $1 AND finished_at IS NULL)",
+        )
+        .bind(spotify_user_id)
+        .fetch_one(&self.pool)
+        .await?)
+    }
+
+    /// Imports that were running when the server stopped will never finish
+    pub async fn interrupt_running_imports(&self) -> Result<()> {
+        sqlx::query(
+            "UPDATE import_jobs SET finished_at = NOW(), error = 'Interrupted' WHERE finished_at IS NULL",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_import_job(
+        &self,
+        spotify_user_id: &String,
+        job_id: i32,
+    ) -> Result<Option<ImportJob>> {
+        Ok(sqlx::query_as::<Postgres, ImportJob>(
+            "SELECT * FROM import_jobs WHERE id = $1 AND spotify_user_id = $2",
+        )
+        .bind(job_id)
+        .bind(spotify_user_id)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    /// The imported tracks of a user that turned out to be anime songs, best matches first
+    pub async fn get_library_anime_tracks(
+        &self,
+        spotify_user_id: &String,
+        source: Option<ImportSource>,
+    ) -> Result<Vec<LibraryTrack>> {
+        let tracks = sqlx::query_as::<Postgres, DBLibraryTrack>(
+            r#"
+                SELECT spotify_id, source, ann_song_ids, certainty, imported_at
+                FROM library_tracks
+                WHERE spotify_user_id = $1
+                    AND certainty IS NOT NULL
+                    AND ($2::int2 IS NULL OR source = $2)
+                ORDER BY certainty DESC, imported_at DESC
+                "#,
+        )
+        .bind(spotify_user_id)
+        .bind(source)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let ann_song_ids: Vec<i32> = tracks
+            .iter()
+            .flat_map(|t| t.ann_song_ids.iter().cloned())
+            .collect();
+
+        let animes: HashMap<i32, DBAnime> = self
+            .get_animes_by_ann_song_ids(&ann_song_ids)
+            .await?
+            .into_iter()
+            .map(|a| (a.ann_song_id, a))
+            .collect();
+
+        Ok(tracks
+            .into_iter()
+            .map(|track| LibraryTrack {
+                anime_info: track
+                    .ann_song_ids
+                    .iter()
+                    .filter_map(|id| animes.get(id))
+                    .map(FrontendAnimeEntry::from_db_anime)
+                    .collect(),
+                spotify_id: track.spotify_id,
+                source: track.source,
+                certainty: track.certainty,
+                imported_at: track.imported_at,
+            })
+            .collect())
+    }
+}
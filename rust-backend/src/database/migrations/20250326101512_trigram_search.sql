@@ -0,0 +1,24 @@
+-- Add migration script here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Normalized romaji forms of the names in new_artists, one row per form so they can be trigram indexed
+CREATE TABLE IF NOT EXISTS artist_search_names (
+    ann_id INTEGER NOT NULL,
+    search_name TEXT NOT NULL,
+    PRIMARY KEY (ann_id, search_name)
+);
+
+CREATE INDEX idx_artist_search_names_trgm ON artist_search_names USING GIN (search_name gin_trgm_ops);
+
+-- Normalized romaji form of song_name
+ALTER TABLE animes ADD COLUMN song_name_search TEXT;
+
+CREATE INDEX idx_anime_song_name_search_trgm ON animes USING GIN (song_name_search gin_trgm_ops);
+
+-- Rough seed for existing rows, the backend writes the properly romanized forms on insert
+INSERT INTO artist_search_names (ann_id, search_name)
+SELECT ann_id, lower(regexp_replace(name, '[^[:alnum:] ]', '', 'g'))
+FROM new_artists, unnest(names) AS name
+ON CONFLICT DO NOTHING;
+
+UPDATE animes SET song_name_search = lower(regexp_replace(song_name, '[^[:alnum:] ]', '', 'g'));
@@ -0,0 +1,18 @@
+-- Add migration script here
+-- Precomputed forms of names so candidates can be scored without running kakasi on every comparison.
+-- *_romaji is the kakasi romanization, *_normalized is the romaji lowercased with all symbols removed
+
+ALTER TABLE new_artists
+    ADD COLUMN romaji_names TEXT[], -- Also contains the unwrapped artist of 'Character (CV: Artist)' names
+    ADD COLUMN normalized_names TEXT[];
+
+-- song_name_search already held the normalized form
+ALTER TABLE animes RENAME COLUMN song_name_search TO song_name_normalized;
+ALTER INDEX idx_anime_song_name_search_trgm RENAME TO idx_anime_song_name_normalized_trgm;
+ALTER TABLE animes ADD COLUMN song_name_romaji TEXT;
+
+ALTER TABLE song_groups
+    ADD COLUMN song_title_romaji TEXT,
+    ADD COLUMN song_title_normalized TEXT;
+
+CREATE INDEX idx_song_groups_song_title_normalized_trgm ON song_groups USING GIN (song_title_normalized gin_trgm_ops);
@@ -0,0 +1,11 @@
+-- Add migration script here
+-- Playlists we have made on spotify, so regenerating one updates it rather than making a duplicate
+CREATE TABLE IF NOT EXISTS generated_playlists (
+    spotify_user_id TEXT NOT NULL,
+    source_key TEXT NOT NULL, -- What the playlist was made from, ex 'anime:1234:0' or 'artist:5678'
+    playlist_id VARCHAR(22) NOT NULL,
+    name TEXT NOT NULL,
+    date_added TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    last_updated TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (spotify_user_id, source_key)
+);
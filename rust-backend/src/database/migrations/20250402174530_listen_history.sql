@@ -0,0 +1,12 @@
+-- Add migration script here
+-- Every identified song a user has listened to, written by /api/update
+CREATE TABLE IF NOT EXISTS listen_history (
+    id BIGSERIAL PRIMARY KEY,
+    spotify_user_id TEXT NOT NULL,
+    spotify_id VARCHAR(22) NOT NULL,
+    ann_song_ids INTEGER[] NOT NULL,
+    certainty INTEGER NOT NULL,
+    listened_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_listen_history_user_time ON listen_history (spotify_user_id, listened_at DESC);
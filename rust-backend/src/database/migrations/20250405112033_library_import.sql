@@ -0,0 +1,23 @@
+-- Add migration script here
+-- Background imports of a users recently played or saved tracks
+CREATE TABLE IF NOT EXISTS import_jobs (
+    id SERIAL PRIMARY KEY,
+    spotify_user_id TEXT NOT NULL,
+    source SMALLINT NOT NULL, -- 0 recently played, 1 saved tracks
+    total INTEGER NOT NULL,
+    processed INTEGER NOT NULL DEFAULT 0,
+    hits INTEGER NOT NULL DEFAULT 0,
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+-- The outcome of every imported track, certainty is NULL when it isn't an anime song
+CREATE TABLE IF NOT EXISTS library_tracks (
+    spotify_user_id TEXT NOT NULL,
+    source SMALLINT NOT NULL,
+    spotify_id VARCHAR(22) NOT NULL,
+    ann_song_ids INTEGER[] NOT NULL,
+    certainty INTEGER,
+    imported_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (spotify_user_id, source, spotify_id)
+);
@@ -0,0 +1,11 @@
+-- Add migration script here
+-- Watch statuses from a users AniList or MyAnimeList, replaced as a whole whenever the list is linked again
+CREATE TABLE IF NOT EXISTS watch_list_entries (
+    spotify_user_id TEXT NOT NULL,
+    source SMALLINT NOT NULL, -- 0 AniList, 1 MyAnimeList
+    anilist_id INTEGER,
+    mal_id INTEGER,
+    status SMALLINT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_watch_list_entries_user ON watch_list_entries (spotify_user_id, source);
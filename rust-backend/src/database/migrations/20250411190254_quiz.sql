@@ -0,0 +1,19 @@
+-- Add migration script here
+-- AMQ style practice quizzes
+CREATE TABLE IF NOT EXISTS quizzes (
+    id SERIAL PRIMARY KEY,
+    spotify_user_id TEXT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS quiz_questions (
+    quiz_id INTEGER NOT NULL REFERENCES quizzes(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    ann_song_id INTEGER NOT NULL,
+    accepted_titles TEXT[] NOT NULL, -- English, Japanese and alternative titles of the anime
+    answer TEXT,
+    score REAL,
+    correct BOOLEAN,
+    answered_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (quiz_id, position)
+);
@@ -0,0 +1,12 @@
+-- Add migration script here
+-- Song details from anisong that we used to drop, filled in for older rows whenever anisong returns them again
+ALTER TABLE animes
+    ADD COLUMN hq_url TEXT,
+    ADD COLUMN mq_url TEXT,
+    ADD COLUMN audio_url TEXT,
+    ADD COLUMN song_difficulty DOUBLE PRECISION,
+    ADD COLUMN song_length DOUBLE PRECISION,
+    ADD COLUMN song_category TEXT,
+    ADD COLUMN is_dub BOOLEAN,
+    ADD COLUMN is_rebroadcast BOOLEAN,
+    ADD COLUMN alt_titles TEXT[];
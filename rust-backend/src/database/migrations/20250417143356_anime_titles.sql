@@ -0,0 +1,14 @@
+-- Add migration script here
+-- Every title of an anime in search_form (romanized, lowercased, no symbols) for trigram title search.
+-- Filled when animes are added, run `main backfill-names` to fill it for existing animes
+ALTER TABLE animes
+    ADD COLUMN title_romaji TEXT,
+    ADD COLUMN title_native TEXT;
+
+CREATE TABLE IF NOT EXISTS anime_title_search (
+    ann_id INTEGER NOT NULL,
+    search_title TEXT NOT NULL,
+    PRIMARY KEY (ann_id, search_title)
+);
+
+CREATE INDEX IF NOT EXISTS idx_anime_title_search_trgm ON anime_title_search USING GIN (search_title gin_trgm_ops);
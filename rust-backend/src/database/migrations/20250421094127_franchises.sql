@@ -0,0 +1,14 @@
+-- Add migration script here
+-- Franchise relations between animes from AniList (sequels, prequels, side stories...).
+-- The franchise of an anime is the lowest AniList id connected to it, filled in whenever its AniList data is fetched
+CREATE TABLE IF NOT EXISTS anime_relations (
+    anilist_id INTEGER NOT NULL,
+    related_id INTEGER NOT NULL,
+    PRIMARY KEY (anilist_id, related_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_anime_relations_related ON anime_relations (related_id);
+
+ALTER TABLE animes ADD COLUMN franchise_id INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_animes_franchise ON animes (franchise_id);
@@ -0,0 +1,15 @@
+-- Add migration script here
+-- Personal tokens for clients that can't use the cookie session, only the sha256 of the token is stored
+CREATE TABLE IF NOT EXISTS api_tokens (
+    id SERIAL PRIMARY KEY,
+    spotify_user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    spotify_refresh_token TEXT, -- Lets the identify scope ask spotify what the user is playing
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_tokens_user ON api_tokens (spotify_user_id);
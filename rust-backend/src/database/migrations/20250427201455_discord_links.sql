@@ -0,0 +1,7 @@
+-- Add migration script here
+-- Discord users that gave the bot one of their API tokens, so it can look up what they are playing
+CREATE TABLE IF NOT EXISTS discord_links (
+    discord_user_id BIGINT PRIMARY KEY,
+    api_token_id INTEGER NOT NULL REFERENCES api_tokens (id) ON DELETE CASCADE,
+    linked_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
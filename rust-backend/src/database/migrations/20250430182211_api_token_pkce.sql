@@ -0,0 +1,3 @@
+-- Add migration script here
+-- Refresh tokens from a PKCE login have to be refreshed without the client secret
+ALTER TABLE api_tokens ADD COLUMN IF NOT EXISTS spotify_pkce BOOLEAN NOT NULL DEFAULT FALSE;
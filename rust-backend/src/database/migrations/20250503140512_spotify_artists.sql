@@ -0,0 +1,15 @@
+-- Add migration script here
+-- The spotify side of artist_links, name comes with every track, the rest from the artists endpoint
+CREATE TABLE IF NOT EXISTS spotify_artists (
+    spotify_id VARCHAR(22) PRIMARY KEY,
+    name TEXT NOT NULL,
+    image_url TEXT,
+    genres TEXT[] NOT NULL DEFAULT '{}',
+    popularity INTEGER,
+    followers INTEGER,
+    fetched_at TIMESTAMPTZ -- NULL until the artists endpoint has been asked
+);
+
+-- How well the names matched when the link was made, NULL for links made before this
+ALTER TABLE artist_links ADD COLUMN IF NOT EXISTS score REAL;
+ALTER TABLE artist_links ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ DEFAULT NOW();
@@ -0,0 +1,7 @@
+-- Add migration script here
+-- Why an import stopped early, and at most one running import per user
+ALTER TABLE import_jobs ADD COLUMN IF NOT EXISTS error TEXT;
+
+UPDATE import_jobs SET finished_at = NOW(), error = 'Interrupted' WHERE finished_at IS NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_import_jobs_running ON import_jobs (spotify_user_id) WHERE finished_at IS NULL;
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- API tokens are always authorized through our own callback with the client secret, never with PKCE
+ALTER TABLE api_tokens DROP COLUMN IF EXISTS spotify_pkce;
@@ -1,21 +1,35 @@
+pub mod api_tokens;
+pub mod backfill;
+pub mod browse;
 pub mod databasetypes;
+pub mod discord;
 pub mod find_anime_no_db;
+pub mod franchises;
+pub mod history;
+pub mod library;
+pub mod playlists;
+pub mod quiz;
 pub mod regex_search;
+pub mod spotify_artists;
+pub mod stats;
+pub mod trigram_search;
+pub mod watch_lists;
 
 use crate::Result;
 use crate::anilist::Media;
 use crate::anisong::{Anime, AnisongClient, Artist};
-use crate::japanese_processing::process_similarity;
+use crate::japanese_processing::{TextVariants, normalized_similarity, search_form};
 use crate::spotify::responses::{SimplifiedArtist, TrackObject};
 use crate::types::{FrontendAnimeEntry, NewSong, SongHit, SongInfo, SongMiss};
 // use axum_sessions::async_session::chrono::Duration;
 use axum_sessions::async_session::log::info;
 use databasetypes::{DBAnime, DBArtist, SongGroup, SongGroupLink};
-use regex_search::{create_artist_regex, process_artist_name};
+use regex_search::{artist_name_variants, process_artist_name};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::{FromRow, Pool, Postgres, QueryBuilder};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::{env, vec};
+use trigram_search::artist_search_names;
 
 pub struct Database {
     pub pool: Pool<Postgres>,
@@ -23,6 +37,8 @@ pub struct Database {
 
 impl Database {
     const ACCURACY_AUTOADD_LIMIT: f32 = 80.0;
+    /// Below this a match is reported as a miss with the candidates instead of a hit
+    pub const ACCURACY_CUTOFF: f32 = 40.0;
     // const UPDATE_TIME: Duration = Duration::days(7);
     // A bound function to initialize the Database. You can call this once on startup.
     pub async fn new() -> Self {
@@ -76,8 +92,7 @@ impl Database {
         )
         .bind(&spotify_id)
         .fetch_all(&self.pool)
-        .await
-        .unwrap())
+        .await?)
     }
 
     async fn get_animes_by_artists_ann_ids(&self, ann_ids: &Vec<i32>) -> Result<Vec<DBAnime>> {
@@ -85,40 +100,52 @@ impl Database {
             sqlx::query_as::<Postgres, DBAnime>("SELECT * FROM animes WHERE artists_ann_id && $1")
                 .bind(&ann_ids)
                 .fetch_all(&self.pool)
-                .await
-                .unwrap(),
+                .await?,
         )
     }
 
+    async fn get_animes_by_composers_ann_ids(&self, ann_ids: &Vec<i32>) -> Result<Vec<DBAnime>> {
+        Ok(sqlx::query_as::<Postgres, DBAnime>(
+            "SELECT * FROM animes WHERE composers_ann_id && $1 OR arrangers_ann_id && $1",
+        )
+        .bind(&ann_ids)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
     pub async fn update_or_add_animes(
         &self,
         animes: Vec<&DBAnime>,
         from_user: Option<String>,
         from_user_mail: Option<String>,
-    ) {
+    ) -> Result<()> {
         if animes.is_empty() {
-            return;
+            return Ok(());
         }
 
         info!("Trying to add or update {} animes", animes.len());
 
         let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
             r#"INSERT INTO animes (
-                ann_id, title_eng, title_jpn, index_type, index_number, anime_type, episodes, mean_score,
+                ann_id, title_eng, title_jpn, title_romaji, title_native, index_type, index_number, anime_type, episodes, mean_score,
                 banner_image, cover_image_color, cover_image_medium, cover_image_large, cover_image_extra_large,
                 media_format, genres, source, studio_ids, studio_names, studio_urls, tag_ids, tag_names, trailer_id,
                 trailer_site, thumbnail, release_season, release_year,
                 ann_song_id, song_name, spotify_artist_ids, artist_names, artists_ann_id, composers_ann_id,
                 arrangers_ann_id, track_index_type, track_index_number, mal_id, anilist_id, anidb_id, kitsu_id, 
-                song_group_id, from_user_name, from_user_mail
+                song_group_id, from_user_name, from_user_mail, song_name_romaji, song_name_normalized,
+                hq_url, mq_url, audio_url, song_difficulty, song_length, song_category, is_dub,
+                is_rebroadcast, alt_titles
             ) "#,
         );
 
-        query_builder.push_values(animes, |mut builder, anime| {
+        query_builder.push_values(animes.iter(), |mut builder, anime| {
             builder
                 .push_bind(&anime.ann_id)
                 .push_bind(&anime.title_eng)
                 .push_bind(&anime.title_jpn)
+                .push_bind(&anime.title_romaji)
+                .push_bind(&anime.title_native)
                 .push_bind(&anime.index_type)
                 .push_bind(&anime.index_number)
                 .push_bind(&anime.anime_type)
@@ -157,11 +184,24 @@ impl Database {
                 .push_bind(&anime.kitsu_id)
                 .push_bind(&anime.song_group_id)
                 .push_bind(&from_user)
-                .push_bind(&from_user_mail);
+                .push_bind(&from_user_mail)
+                .push_bind(&anime.song_name_romaji)
+                .push_bind(&anime.song_name_normalized)
+                .push_bind(&anime.hq_url)
+                .push_bind(&anime.mq_url)
+                .push_bind(&anime.audio_url)
+                .push_bind(&anime.song_difficulty)
+                .push_bind(&anime.song_length)
+                .push_bind(&anime.song_category)
+                .push_bind(&anime.is_dub)
+                .push_bind(&anime.is_rebroadcast)
+                .push_bind(&anime.alt_titles);
         });
 
         query_builder.push(
         r#"ON CONFLICT (ann_song_id) DO UPDATE SET 
+            title_romaji = COALESCE(EXCLUDED.title_romaji, animes.title_romaji),
+            title_native = COALESCE(EXCLUDED.title_native, animes.title_native),
             episodes = COALESCE(EXCLUDED.episodes, animes.episodes),
             mean_score = COALESCE(EXCLUDED.mean_score, animes.mean_score),
             banner_image = COALESCE(EXCLUDED.banner_image, animes.banner_image),
@@ -183,12 +223,26 @@ impl Database {
             release_season = COALESCE(EXCLUDED.release_season, animes.release_season),
             release_year = COALESCE(EXCLUDED.release_year, animes.release_year),
             song_group_id = COALESCE(EXCLUDED.song_group_id, animes.song_group_id),
+            song_name_romaji = COALESCE(EXCLUDED.song_name_romaji, animes.song_name_romaji),
+            song_name_normalized = COALESCE(EXCLUDED.song_name_normalized, animes.song_name_normalized),
+            hq_url = COALESCE(EXCLUDED.hq_url, animes.hq_url),
+            mq_url = COALESCE(EXCLUDED.mq_url, animes.mq_url),
+            audio_url = COALESCE(EXCLUDED.audio_url, animes.audio_url),
+            song_difficulty = COALESCE(EXCLUDED.song_difficulty, animes.song_difficulty),
+            song_length = COALESCE(EXCLUDED.song_length, animes.song_length),
+            song_category = COALESCE(EXCLUDED.song_category, animes.song_category),
+            is_dub = COALESCE(EXCLUDED.is_dub, animes.is_dub),
+            is_rebroadcast = COALESCE(EXCLUDED.is_rebroadcast, animes.is_rebroadcast),
+            alt_titles = COALESCE(EXCLUDED.alt_titles, animes.alt_titles),
             last_updated = EXCLUDED.last_updated"#
             );
 
         let query = query_builder.build();
 
-        query.execute(&self.pool).await.unwrap();
+        query.execute(&self.pool).await?;
+
+        self.add_anime_search_titles(&animes).await?;
+        Ok(())
     }
 
     pub async fn add_song_group_link(
@@ -196,15 +250,14 @@ impl Database {
         spotify_id: &String,
         song_title: &String,
         artist_ids: &Vec<i32>,
-    ) -> i32 {
+    ) -> Result<i32> {
         let song_link = sqlx::query_as!(
             SongGroupLink,
             "SELECT * FROM song_group_links WHERE spotify_id = $1",
             spotify_id
         )
         .fetch_optional(&self.pool)
-        .await
-        .unwrap();
+        .await?;
         if song_link.is_none() {
             let song_group = sqlx::query_as!(
                 SongGroup,
@@ -213,26 +266,28 @@ impl Database {
                 artist_ids,
             )
             .fetch_optional(&self.pool)
-            .await
-            .unwrap();
+            .await?;
             let group_id = if song_group.is_some() {
                 song_group.unwrap().group_id
             } else {
+                let title_variants = TextVariants::new(song_title);
                 let group_id = sqlx::query!(
-                    "INSERT INTO song_groups (song_title, artist_ids) VALUES ($1, $2) ON CONFLICT DO NOTHING RETURNING group_id",
+                    "INSERT INTO song_groups (song_title, artist_ids, song_title_romaji, song_title_normalized) VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING RETURNING group_id",
                     song_title,
-                    artist_ids
-                ).fetch_one(&self.pool).await.unwrap();
+                    artist_ids,
+                    title_variants.romaji,
+                    title_variants.normalized,
+                ).fetch_one(&self.pool).await?;
                 group_id.group_id
             };
-            let _ = sqlx::query!(
+            sqlx::query!(
                 "INSERT INTO song_group_links (spotify_id, group_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
                 spotify_id,
                 group_id
-            ).execute(&self.pool).await;
-            group_id
+            ).execute(&self.pool).await?;
+            Ok(group_id)
         } else {
-            song_link.unwrap().group_id
+            Ok(song_link.unwrap().group_id)
         }
     }
 
@@ -277,13 +332,13 @@ impl Database {
                     .map(|a| a.id)
                     .collect::<Vec<i32>>(),
             )
-            .await;
+            .await?;
 
         let db_anime =
             DBAnime::from_anisong_and_anilist(&anisong_anime, media.as_ref(), Some(group_id));
 
         self.update_or_add_animes(vec![&db_anime], from_user_name, from_user_mail)
-            .await;
+            .await?;
         Ok(())
     }
 
@@ -291,30 +346,17 @@ impl Database {
         &self,
         track: &TrackObject,
     ) -> Result<(Vec<DBAnime>, Vec<DBAnime>, Vec<i32>, Vec<DBArtist>, f32)> {
-        let anime = self.get_anime_by_spotify_id(&track.id).await.unwrap();
+        let anime = self.get_anime_by_spotify_id(&track.id).await?;
         let artists = self
             .get_artists_spotify_id(&track.artists.iter().map(|a| a.id.clone()).collect())
-            .await
-            .unwrap();
+            .await?;
 
         let artist_ann_ids = if anime.len() > 0 {
             anime[0].artists_ann_id.clone()
         } else if artists.len() > 0 {
             artists.iter().map(|a| a.ann_id).collect()
         } else {
-            let artists = sqlx::query_as::<Postgres, DBArtist>(
-                "SELECT * FROM new_artists WHERE EXISTS (
-                    SELECT 1
-                    FROM unnest(names) AS name WHERE name ~* $1);",
-            )
-            .bind(create_artist_regex(
-                track.artists.iter().map(|a| &a.name).collect(),
-            ))
-            .fetch_all(&self.pool)
-            .await
-            .unwrap();
-
-            artists.iter().map(|a| a.ann_id).collect()
+            self.search_artist_ids_for_track(track).await?
         };
 
         let mut temp = artist_ann_ids.clone();
@@ -323,13 +365,19 @@ impl Database {
             .filter_map(|a| a.groups_ids.clone())
             .for_each(|g| temp.extend(g));
 
-        let mut more_by_artists = self.get_animes_by_artists_ann_ids(&temp).await.unwrap();
+        let mut more_by_artists = self.get_animes_by_artists_ann_ids(&temp).await?;
+        if more_by_artists.is_empty() && anime.is_empty() {
+            // Spotify often credits the composer instead of the performer, especially for OSTs
+            more_by_artists = self
+                .get_animes_by_composers_ann_ids(&artist_ann_ids)
+                .await?;
+        }
 
         if anime.len() > 0 {
             Ok((anime, more_by_artists, artist_ann_ids, artists, 100.0))
         } else if more_by_artists.len() > 0 {
             let (best_match, certainty) =
-                DBAnime::pick_best_by_song_name(&mut more_by_artists, &track.name).unwrap();
+                DBAnime::pick_best_by_song_name(&mut more_by_artists, &track.name)?;
 
             if certainty > Self::ACCURACY_AUTOADD_LIMIT {
                 self.add_song_group_link(
@@ -337,7 +385,7 @@ impl Database {
                     &best_match[0].song_name,
                     &best_match[0].artists_ann_id,
                 )
-                .await;
+                .await?;
             }
             Ok((
                 best_match.into_iter().map(|a| a.clone()).collect(),
@@ -398,10 +446,14 @@ impl Database {
         &self,
         anisong_artists: &Vec<Artist>,
         spotify_artists: &Vec<SimplifiedArtist>,
-    ) {
+    ) -> Result<()> {
+        // Spotify names come with every track, keep them even when nothing gets linked
+        self.save_spotify_artist_names(&spotify_artists.iter().collect::<Vec<_>>())
+            .await?;
+
         // Fetch already existing links to make better choices
         let existing_artist_links = sqlx::query_as::<Postgres, (i32, String)>(
-            "SELECT * FROM artist_links WHERE spotify_id = ANY($1)",
+            "SELECT ann_id, spotify_id FROM artist_links WHERE spotify_id = ANY($1)",
         )
         .bind(
             spotify_artists
@@ -410,8 +462,7 @@ impl Database {
                 .collect::<Vec<String>>(),
         )
         .fetch_all(&self.pool)
-        .await
-        .unwrap();
+        .await?;
 
         // make arrays of references to the artists so we can shuffle them around as we wish
         let mut anisong_artists: Vec<&Artist> = anisong_artists.iter().collect();
@@ -425,44 +476,52 @@ impl Database {
 
         let mut links = Vec::new();
 
+        // Romanize the spotify names once rather than for every comparison
+        let spotify_names: Vec<(&SimplifiedArtist, String)> = spotify_artists
+            .iter()
+            .map(|&a| (a, search_form(&process_artist_name(&a.name))))
+            .collect();
+
         // Find best match
         for artist in anisong_artists.clone() {
-            let mut eval_spotify: Vec<(&SimplifiedArtist, f32)> = spotify_artists
+            let anisong_names = artist_search_names(&artist.names);
+            let mut eval_spotify: Vec<(&SimplifiedArtist, f32)> = spotify_names
                 .iter()
-                .map(|&a| {
-                    let max_score = artist
-                        .names
+                .map(|(a, spotify_name)| {
+                    let max_score = anisong_names
                         .iter()
-                        .map(|an| process_similarity(&process_artist_name(&a.name), &an))
-                        .max_by(|a, b| a.partial_cmp(b).unwrap())
-                        .unwrap();
-                    (a, max_score)
+                        .map(|an| normalized_similarity(spotify_name, an))
+                        .fold(0.0, f32::max);
+                    (*a, max_score)
                 })
                 .collect();
 
             eval_spotify.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
 
             if !eval_spotify.is_empty() && eval_spotify[0].1 > Self::ACCURACY_AUTOADD_LIMIT {
-                links.push((artist.id, eval_spotify[0].0.id.clone()));
+                links.push((artist.id, eval_spotify[0].0.id.clone(), eval_spotify[0].1));
             }
         }
 
-        let mut tx = self.pool.begin().await.unwrap();
+        let mut tx = self.pool.begin().await?;
 
         if !links.is_empty() {
             info!("Binding {} artists", links.len());
 
             // Insert links
             let mut query_builder: QueryBuilder<Postgres> =
-                QueryBuilder::new(r#"Insert into artist_links (ann_id, spotify_id) "#);
+                QueryBuilder::new(r#"Insert into artist_links (ann_id, spotify_id, score) "#);
 
             query_builder.push_values(links, |mut builder, link| {
-                builder.push_bind(link.0).push_bind(link.1);
+                builder
+                    .push_bind(link.0)
+                    .push_bind(link.1)
+                    .push_bind(link.2);
             });
 
             query_builder.push(" ON CONFLICT DO NOTHING");
 
-            query_builder.build().execute(&mut *tx).await.unwrap();
+            query_builder.build().execute(&mut *tx).await?;
         }
 
         // Insert all artists as these could still be usefull without links
@@ -470,10 +529,11 @@ impl Database {
         if !anisong_artists.is_empty() {
             info!("Adding {} artists to the database", anisong_artists.len());
             let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
-                r#"INSERT INTO new_artists (ann_id, names, groups_ids, members) "#,
+                r#"INSERT INTO new_artists (ann_id, names, groups_ids, members, romaji_names, normalized_names) "#,
             );
 
-            query_builder.push_values(anisong_artists, |mut builder, artist| {
+            query_builder.push_values(anisong_artists.iter(), |mut builder, artist| {
+                let variants = artist_name_variants(&artist.names);
                 builder
                     .push_bind(artist.id)
                     .push_bind(artist.names.clone())
@@ -488,16 +548,32 @@ impl Database {
                             .members
                             .as_ref()
                             .map(|o| o.iter().map(|a| a.id).collect::<Vec<i32>>()),
+                    )
+                    .push_bind(
+                        variants
+                            .iter()
+                            .map(|v| v.romaji.clone())
+                            .collect::<Vec<String>>(),
+                    )
+                    .push_bind(
+                        variants
+                            .into_iter()
+                            .map(|v| v.normalized)
+                            .collect::<Vec<String>>(),
                     );
             });
 
             query_builder.push(" ON CONFLICT DO NOTHING");
 
             let query = query_builder.build();
-            query.execute(&mut *tx).await.unwrap();
+            query.execute(&mut *tx).await?;
         }
 
-        tx.commit().await.unwrap();
+        tx.commit().await?;
+
+        self.add_artist_search_names(anisong_artists.iter().map(|a| (a.id, &a.names)).collect())
+            .await?;
+        Ok(())
     }
 
     pub async fn merge(
@@ -547,8 +623,7 @@ impl Database {
                 .collect::<Vec<i32>>(),
         )
         .fetch_all(&self.pool)
-        .await
-        .unwrap();
+        .await?;
 
         // push it to the correct vec and filter it out form anisong vecs.
         let mut anisong_filter = HashSet::with_capacity(found_anime.len());
@@ -559,6 +634,13 @@ impl Database {
                 _ => more_by_artist_db.push(db_anime.anime),
             }
         }
+        // Keep what anisong said about songs we already have so older rows can be filled in
+        let known_anisongs: HashMap<i32, Anime> = anime_hits_anisong
+            .iter()
+            .chain(more_by_artist_anisong.iter())
+            .filter(|a| anisong_filter.contains(&a.annSongId))
+            .map(|a| (a.annSongId, a.clone()))
+            .collect();
         anime_hits_anisong.retain(|a| anisong_filter.insert(a.annSongId));
         more_by_artist_anisong.retain(|a| anisong_filter.insert(a.annSongId));
 
@@ -590,19 +672,24 @@ impl Database {
         );
         let anilist_ids = Vec::from_iter(anilist_ids_set.into_iter());
         // fetch all media
-        let media = Media::fetch_many(anilist_ids).await.unwrap();
+        let media = Media::fetch_many(anilist_ids).await?;
 
         // Promote the anisongs Anime to DBAnime
         let mut promoted_anisong_hit =
-            DBAnime::from_anisongs_and_anilists(&anime_hits_anisong, &media, song_group_id)
-                .unwrap();
+            DBAnime::from_anisongs_and_anilists(&anime_hits_anisong, &media, song_group_id)?;
         let mut promoted_anisong_more_by_artist =
-            DBAnime::from_anisongs_and_anilists(&more_by_artist_anisong, &media, None).unwrap();
+            DBAnime::from_anisongs_and_anilists(&more_by_artist_anisong, &media, None)?;
 
         // Update existing DBAnime and collect the copies of the Updated DBAnime
-        let mut update_copies = DBAnime::update_all(&mut anime_hits_db, &media, song_group_id);
+        let mut update_copies =
+            DBAnime::update_all(&mut anime_hits_db, &media, &known_anisongs, song_group_id);
 
-        update_copies.extend(DBAnime::update_all(&mut more_by_artist_db, &media, None));
+        update_copies.extend(DBAnime::update_all(
+            &mut more_by_artist_db,
+            &media,
+            &known_anisongs,
+            None,
+        ));
 
         // Collect refs to everything that needs to be sent to the database
         let mut updates_or_adds = update_copies.iter().collect::<Vec<&DBAnime>>();
@@ -611,12 +698,19 @@ impl Database {
 
         // Send to database
         self.update_or_add_animes(updates_or_adds, Some("Database".to_string()), None)
-            .await;
+            .await?;
 
         // Assemble all hits and more_by_artist entries
         anime_hits_db.append(&mut promoted_anisong_hit);
         more_by_artist_db.append(&mut promoted_anisong_more_by_artist);
 
+        let franchises = self.save_franchise_relations(&media).await?;
+        for anime in anime_hits_db.iter_mut().chain(more_by_artist_db.iter_mut()) {
+            if let Some(franchise_id) = anime.anilist_id.and_then(|id| franchises.get(&id.0)) {
+                anime.franchise_id = Some(*franchise_id);
+            }
+        }
+
         Ok((anime_hits_db, more_by_artist_db))
     }
 
@@ -627,13 +721,12 @@ impl Database {
         accuracy_cutoff: f32,
     ) -> Result<NewSong> {
         let (mut hit_anime, more_by_artists, artists_ann_id, _, certainty) =
-            self.db_full_search(track).await.unwrap();
+            self.db_full_search(track).await?;
 
         if certainty == 100.0 {
             let anisong_animes = anisong_db
                 .get_animes_by_artists_ids::<false>(artists_ann_id)
-                .await
-                .unwrap();
+                .await?;
 
             // split anisongs into hits and misses and add more ids
             let (anisong_anime_hits, anisong_anime_more): (Vec<Anime>, Vec<Anime>) =
@@ -645,7 +738,7 @@ impl Database {
 
             // Add artists and try and add artist links
             if let Some(artists) = anisong_anime_hits.first().map(|a| &a.artists) {
-                self.try_add_artists(&artists, &track.artists).await;
+                self.try_add_artists(&artists, &track.artists).await?;
             }
 
             // get group id.
@@ -655,7 +748,7 @@ impl Database {
                     &hit_anime[0].song_name,
                     &hit_anime[0].artists_ann_id,
                 )
-                .await;
+                .await?;
 
             let (mut hit_anime, mut more_by_artists) = self
                 .merge(
@@ -665,8 +758,7 @@ impl Database {
                     anisong_anime_more,
                     Some(group_id),
                 )
-                .await
-                .unwrap();
+                .await?;
 
             more_by_artists.sort_by(|a, b| a.title_eng.cmp(&b.title_eng));
             hit_anime.sort_by(|a, b| a.title_eng.cmp(&b.title_eng));
@@ -683,16 +775,35 @@ impl Database {
                     .iter()
                     .map(|a| FrontendAnimeEntry::from_db_anime(a))
                     .collect(),
+                franchises: None,
             }))
         } else {
             if artists_ann_id.len() > 0 {
                 let mut anisongs = anisong_db
                     .get_animes_by_artists_ids::<false>(artists_ann_id.clone())
-                    .await
-                    .unwrap();
-
-                let (mut anime_hits, score) =
-                    AnisongClient::pick_best_by_song_name(&mut anisongs, &track.name).unwrap();
+                    .await?;
+
+                let (mut anime_hits, mut score) =
+                    AnisongClient::pick_best_by_song_name(&mut anisongs, &track.name)?;
+
+                // The performers did not match, spotify might be crediting the composer or arranger
+                let mut found_by_composer = false;
+                if score <= accuracy_cutoff {
+                    let mut composed = anisong_db
+                        .get_animes_by_composers_ids(artists_ann_id.clone(), true)
+                        .await?;
+
+                    let (composed_hits, composed_score) =
+                        AnisongClient::pick_best_by_song_name(&mut composed, &track.name)?;
+
+                    if composed_score > score {
+                        anisongs.append(&mut anime_hits);
+                        anime_hits = composed_hits;
+                        score = composed_score;
+                        found_by_composer = true;
+                    }
+                    anisongs.append(&mut composed);
+                }
 
                 // Add constant for acceptable match
                 if score > accuracy_cutoff {
@@ -719,8 +830,7 @@ impl Database {
                     if !missing_artists.is_empty() {
                         let additional_anisongs = anisong_db
                             .get_animes_by_artists_ids::<false>(missing_artists)
-                            .await
-                            .unwrap();
+                            .await?;
                         let (mut more_hits, mut more_by_artist): (Vec<Anime>, Vec<Anime>) =
                             additional_anisongs.into_iter().partition(|a| {
                                 a.artists.iter().map(|a| a.id).collect::<Vec<i32>>()
@@ -731,10 +841,11 @@ impl Database {
                         anisongs.append(&mut more_by_artist);
                     }
 
-                    // Try and add more artists to the database
-                    if score > Self::ACCURACY_AUTOADD_LIMIT {
+                    // Try and add more artists to the database. artist_links are for performers only,
+                    // a composer hit would link the spotify performers to the composers
+                    if score > Self::ACCURACY_AUTOADD_LIMIT && !found_by_composer {
                         self.try_add_artists(&anime_hits[0].artists, &track.artists)
-                            .await;
+                            .await?;
                     }
 
                     let group_id = if score > Self::ACCURACY_AUTOADD_LIMIT {
@@ -744,7 +855,7 @@ impl Database {
                                 &anime_hits[0].songName,
                                 &anime_hits[0].artists.iter().map(|a| a.id).collect(),
                             )
-                            .await,
+                            .await?,
                         )
                     } else {
                         None
@@ -752,8 +863,7 @@ impl Database {
 
                     let (mut anime_hit, mut more_by_artists) = self
                         .merge(vec![], more_by_artists, anime_hits, anisongs, group_id)
-                        .await
-                        .unwrap();
+                        .await?;
 
                     more_by_artists.sort_by(|a, b| a.title_eng.cmp(&b.title_eng));
                     anime_hit.sort_by(|a, b| a.title_eng.cmp(&b.title_eng));
@@ -770,12 +880,12 @@ impl Database {
                             .iter()
                             .map(|a| FrontendAnimeEntry::from_db_anime(a))
                             .collect(),
+                        franchises: None,
                     }))
                 } else {
                     let (_, mut possible) = self
                         .merge(vec![], more_by_artists, vec![], anisongs, None)
-                        .await
-                        .unwrap();
+                        .await?;
 
                     possible.append(&mut hit_anime);
 
@@ -791,8 +901,7 @@ impl Database {
                 info!("It is a sad moment for the database");
                 return Ok(self
                     .find_most_likely_anime(track, accuracy_cutoff, anisong_db)
-                    .await
-                    .unwrap());
+                    .await?);
             }
         }
     }
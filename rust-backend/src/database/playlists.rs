@@ -0,0 +1,124 @@
+use super::Database;
+use crate::Result;
+use crate::types::TrackType;
+use axum_sessions::async_session::chrono::{DateTime, Utc};
+use sqlx::Postgres;
+use std::collections::{HashMap, HashSet};
+
+impl Database {
+    /// Song groups of an anime in track order, optionally only of one track type
+    pub async fn get_group_ids_by_ann_id(
+        &self,
+        ann_id: i32,
+        track_type: Option<TrackType>,
+    ) -> Result<Vec<i32>> {
+        Ok(sqlx::query_scalar::<Postgres, i32>(
+            r#"
+                SELECT song_group_id
+                FROM animes
+                WHERE ann_id = $1
+                    AND song_group_id IS NOT NULL
+                    AND ($2::int2 IS NULL OR track_index_type = $2)
+                ORDER BY track_index_type, track_index_number
+                "#,
+        )
+        .bind(ann_id)
+        .bind(track_type.map(|t| t.discriminant()))
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Song groups the user listened to within the range, most recently heard first
+    pub async fn get_group_ids_by_history(
+        &self,
+        spotify_user_id: &String,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        track_type: Option<TrackType>,
+    ) -> Result<Vec<i32>> {
+        Ok(sqlx::query_scalar::<Postgres, i32>(
+            r#"
+                SELECT animes.song_group_id
+                FROM listen_history AS history
+                JOIN animes ON animes.ann_song_id = ANY(history.ann_song_ids)
+                WHERE history.spotify_user_id = $1
+                    AND animes.song_group_id IS NOT NULL
+                    AND ($2::timestamptz IS NULL OR history.listened_at >= $2)
+                    AND ($3::timestamptz IS NULL OR history.listened_at < $3)
+                    AND ($4::int2 IS NULL OR animes.track_index_type = $4)
+                GROUP BY animes.song_group_id
+                ORDER BY MAX(history.listened_at) DESC
+                "#,
+        )
+        .bind(spotify_user_id)
+        .bind(since)
+        .bind(until)
+        .bind(track_type.map(|t| t.discriminant()))
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// One spotify track for every song group that has one, in the order of the given groups
+    pub async fn get_playlist_spotify_ids(&self, group_ids: &Vec<i32>) -> Result<Vec<String>> {
+        let links: HashMap<i32, String> = sqlx::query_as::<Postgres, (i32, String)>(
+            r#"
+                SELECT DISTINCT ON (group_id) group_id, spotify_id
+                FROM song_group_links
+                WHERE group_id = ANY($1)
+                ORDER BY group_id, spotify_id
+                "#,
+        )
+        .bind(group_ids)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .collect();
+
+        let mut seen = HashSet::with_capacity(group_ids.len());
+        Ok(group_ids
+            .iter()
+            .filter(|&&id| seen.insert(id))
+            .filter_map(|id| links.get(id).cloned())
+            .collect())
+    }
+
+    pub async fn get_generated_playlist(
+        &self,
+        spotify_user_id: &String,
+        source_key: &String,
+    ) -> Result<Option<String>> {
+        Ok(sqlx::query_scalar::<Postgres, String>(
+            "SELECT playlist_id FROM generated_playlists WHERE spotify_user_id = $1 AND source_key = $2",
+        )
+        .bind(spotify_user_id)
+        .bind(source_key)
+        .fetch_optional(&self.pool)
+        .await?)
+    }
+
+    pub async fn save_generated_playlist(
+        &self,
+        spotify_user_id: &String,
+        source_key: &String,
+        playlist_id: &String,
+        name: &String,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+                INSERT INTO generated_playlists (spotify_user_id, source_key, playlist_id, name)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (spotify_user_id, source_key) DO UPDATE SET
+                    playlist_id = EXCLUDED.playlist_id,
+                    name = EXCLUDED.name,
+                    last_updated = NOW()
+                "#,
+        )
+        .bind(spotify_user_id)
+        .bind(source_key)
+        .bind(playlist_id)
+        .bind(name)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
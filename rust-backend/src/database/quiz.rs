@@ -0,0 +1,154 @@
+use super::Database;
+use super::databasetypes::DBAnime;
+use crate::types::TrackType;
+use crate::{Error, Result};
+use sqlx::{FromRow, Postgres, QueryBuilder};
+
+/// Which songs a quiz may pick from, None means no filter
+pub struct QuizFilter {
+    pub history_of: Option<String>,
+    pub year_min: Option<i32>,
+    pub year_max: Option<i32>,
+    pub track_type: Option<TrackType>,
+    pub difficulty_min: Option<f64>,
+    pub difficulty_max: Option<f64>,
+}
+
+#[derive(FromRow)]
+pub struct DBQuizQuestion {
+    pub position: i32,
+    pub ann_song_id: i32,
+    pub accepted_titles: Vec<String>,
+    pub answer: Option<String>,
+    pub score: Option<f32>,
+    pub correct: Option<bool>,
+}
+
+impl Database {
+    /// Random songs with audio matching the filter, picked from the users listening history if one is given
+    pub async fn get_quiz_candidates(
+        &self,
+        filter: &QuizFilter,
+        limit: i64,
+    ) -> Result<Vec<DBAnime>> {
+        Ok(sqlx::query_as::<Postgres, DBAnime>(
+            r#"
+                SELECT *
+                FROM animes
+                WHERE ($1::text IS NULL OR ann_song_id IN (
+                        SELECT unnest(ann_song_ids) FROM listen_history WHERE spotify_user_id = $1
+                    ))
+                    AND ($2::int4 IS NULL OR release_year >= $2)
+                    AND ($3::int4 IS NULL OR release_year <= $3)
+                    AND ($4::int2 IS NULL OR track_index_type = $4)
+                    AND ($5::float8 IS NULL OR song_difficulty >= $5)
+                    AND ($6::float8 IS NULL OR song_difficulty <= $6)
+                    AND (audio_url IS NOT NULL OR mq_url IS NOT NULL OR hq_url IS NOT NULL)
+                ORDER BY random()
+                LIMIT $7
+                "#,
+        )
+        .bind(&filter.history_of)
+        .bind(filter.year_min)
+        .bind(filter.year_max)
+        .bind(filter.track_type.map(|t| t.discriminant()))
+        .bind(filter.difficulty_min)
+        .bind(filter.difficulty_max)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Makes a quiz out of (ann song id, accepted titles) in question order
+    pub async fn create_quiz(
+        &self,
+        spotify_user_id: &String,
+        questions: &Vec<(i32, Vec<String>)>,
+    ) -> Result<i32> {
+        let mut transaction = self.pool.begin().await?;
+
+        let quiz_id = sqlx::query_scalar::<Postgres, i32>(
+            "INSERT INTO quizzes (spotify_user_id) VALUES ($1) RETURNING id",
+        )
+        .bind(spotify_user_id)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+            "INSERT INTO quiz_questions (quiz_id, position, ann_song_id, accepted_titles) ",
+        );
+        query_builder.push_values(
+            questions.iter().enumerate(),
+            |mut builder, (position, (ann_song_id, titles))| {
+                builder
+                    .push_bind(quiz_id)
+                    .push_bind(position as i32)
+                    .push_bind(ann_song_id)
+                    .push_bind(titles);
+            },
+        );
+        query_builder.build().execute(&mut *transaction).await?;
+
+        transaction.commit().await?;
+        Ok(quiz_id)
+    }
+
+    /// The questions of a quiz in order, None if the user has no such quiz
+    pub async fn get_quiz_questions(
+        &self,
+        spotify_user_id: &String,
+        quiz_id: i32,
+    ) -> Result<Option<Vec<DBQuizQuestion>>> {
+        let questions = sqlx::query_as::<Postgres, DBQuizQuestion>(
+            r#"
+                SELECT questions.position, questions.ann_song_id, questions.accepted_titles,
+                    questions.answer, questions.score, questions.correct
+                FROM quiz_questions AS questions
+                JOIN quizzes ON quizzes.id = questions.quiz_id
+                WHERE quizzes.id = $1 AND quizzes.spotify_user_id = $2
+                ORDER BY questions.position
+                "#,
+        )
+        .bind(quiz_id)
+        .bind(spotify_user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        // Quizzes are never made without questions
+        Ok(if questions.is_empty() {
+            None
+        } else {
+            Some(questions)
+        })
+    }
+
+    /// Saves the first answer to a question, later answers are refused even when sent at the same time
+    pub async fn save_quiz_answer(
+        &self,
+        quiz_id: i32,
+        position: i32,
+        answer: &String,
+        score: f32,
+        correct: bool,
+    ) -> Result<()> {
+        let result = sqlx::query(
+            r#"
+                UPDATE quiz_questions
+                SET answer = $3, score = $4, correct = $5, answered_at = NOW()
+                WHERE quiz_id = $1 AND position = $2 AND answer IS NULL
+                "#,
+        )
+        .bind(quiz_id)
+        .bind(position)
+        .bind(answer)
+        .bind(score)
+        .bind(correct)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::Conflict("Question already answered".to_string()));
+        }
+        Ok(())
+    }
+}
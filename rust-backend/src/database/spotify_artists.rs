@@ -0,0 +1,190 @@
+use super::Database;
+use crate::Result;
+use crate::spotify::api::{ClientTokenManager, get_artists};
+use crate::spotify::responses::{ArtistObject, SimplifiedArtist};
+use crate::types::{ArtistLinkAudit, SpotifyArtist};
+use log::{info, warn};
+use sqlx::{Postgres, QueryBuilder};
+use std::collections::HashSet;
+
+impl Database {
+    const MAX_ARTISTS_PER_REQUEST: usize = 50;
+    // Genres and images hardly change, popularity does but is only shown as a hint
+    const SPOTIFY_ARTIST_REFRESH_DAYS: i32 = 30;
+
+    /// Keeps the names spotify gives with every track, so links can be checked without asking spotify
+    pub async fn save_spotify_artist_names(&self, artists: &[&SimplifiedArtist]) -> Result<()> {
+        if artists.is_empty() {
+            return Ok(());
+        }
+
+        // Postgres can't update the same row twice in one upsert, and tracks can credit an artist twice
+        let mut seen = HashSet::new();
+        let artists: Vec<&SimplifiedArtist> = artists
+            .iter()
+            .copied()
+            .filter(|artist| seen.insert(&artist.id))
+            .collect();
+
+        let mut query_builder: QueryBuilder<Postgres> =
+            QueryBuilder::new("INSERT INTO spotify_artists (spotify_id, name) ");
+        query_builder.push_values(artists, |mut builder, artist| {
+            builder
+                .push_bind(artist.id.clone())
+                .push_bind(artist.name.clone());
+        });
+        query_builder.push(" ON CONFLICT (spotify_id) DO UPDATE SET name = EXCLUDED.name");
+
+        query_builder.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Stores everything the artists endpoint told us
+    pub async fn save_spotify_artists(&self, artists: &[ArtistObject]) -> Result<()> {
+        if artists.is_empty() {
+            return Ok(());
+        }
+
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+            "INSERT INTO spotify_artists (spotify_id, name, image_url, genres, popularity, followers, fetched_at) ",
+        );
+        query_builder.push_values(artists, |mut builder, artist| {
+            builder
+                .push_bind(artist.id.clone())
+                .push_bind(artist.name.clone())
+                // Spotify orders images widest first
+                .push_bind(artist.images.first().map(|i| i.url.clone()))
+                .push_bind(artist.genres.clone())
+                .push_bind(artist.popularity as i32)
+                .push_bind(artist.followers.total as i32)
+                .push("NOW()");
+        });
+        query_builder.push(
+            " ON CONFLICT (spotify_id) DO UPDATE SET
+                name = EXCLUDED.name,
+                image_url = EXCLUDED.image_url,
+                genres = EXCLUDED.genres,
+                popularity = EXCLUDED.popularity,
+                followers = EXCLUDED.followers,
+                fetched_at = EXCLUDED.fetched_at",
+        );
+
+        query_builder.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// The spotify artists an anisong artist is linked to
+    pub async fn get_linked_spotify_artists(&self, ann_id: i32) -> Result<Vec<SpotifyArtist>> {
+        Ok(sqlx::query_as::<Postgres, SpotifyArtist>(
+            r#"
+                SELECT spotify_artists.spotify_id, name, image_url, genres, popularity, followers
+                FROM artist_links
+                JOIN spotify_artists ON spotify_artists.spotify_id = artist_links.spotify_id
+                WHERE artist_links.ann_id = $1 AND spotify_artists.name <> ''
+                ORDER BY popularity DESC NULLS LAST
+                "#,
+        )
+        .bind(ann_id)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+
+    /// Remembers that spotify was asked about artists it doesn't know, so they don't get asked about every run.
+    /// Artists only known from old links have no name, spotify won't give one either
+    async fn save_unknown_spotify_artists(&self, spotify_ids: &[String]) -> Result<()> {
+        if spotify_ids.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query(
+            r#"
+                INSERT INTO spotify_artists (spotify_id, name, fetched_at)
+                SELECT spotify_id, '', NOW() FROM unnest($1::text[]) AS spotify_id
+                ON CONFLICT (spotify_id) DO UPDATE SET fetched_at = EXCLUDED.fetched_at
+                "#,
+        )
+        .bind(spotify_ids)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Fetches the details of linked spotify artists we never asked spotify about, or not for a while.
+    /// Returns how many artists were updated
+    pub async fn enrich_spotify_artists(
+        &self,
+        client_token: &ClientTokenManager,
+        max_artists: i64,
+    ) -> Result<usize> {
+        let spotify_ids = sqlx::query_scalar::<Postgres, String>(
+            r#"
+                SELECT artist_links.spotify_id
+                FROM artist_links
+                LEFT JOIN spotify_artists ON spotify_artists.spotify_id = artist_links.spotify_id
+                WHERE spotify_artists.fetched_at IS NULL
+                    OR spotify_artists.fetched_at < NOW() - make_interval(days => $1)
+                GROUP BY artist_links.spotify_id, spotify_artists.fetched_at
+                ORDER BY spotify_artists.fetched_at ASC NULLS FIRST, artist_links.spotify_id
+                LIMIT $2
+                "#,
+        )
+        .bind(Self::SPOTIFY_ARTIST_REFRESH_DAYS)
+        .bind(max_artists)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if spotify_ids.is_empty() {
+            return Ok(0);
+        }
+        info!("Fetching {} spotify artists", spotify_ids.len());
+
+        let token = client_token.access_token().await?;
+        let mut updated = 0;
+        for chunk in spotify_ids.chunks(Self::MAX_ARTISTS_PER_REQUEST) {
+            // Spotify answers in the order it was asked, with null for ids it doesn't know
+            let mut artists = Vec::new();
+            let mut unknown = Vec::new();
+            for (spotify_id, artist) in chunk.iter().zip(get_artists(chunk, &token).await?) {
+                match artist {
+                    Some(artist) => artists.push(artist),
+                    None => unknown.push(spotify_id.clone()),
+                }
+            }
+            if !unknown.is_empty() {
+                warn!("Spotify doesn't know linked artists {:?}", unknown);
+            }
+            self.save_spotify_artists(&artists).await?;
+            self.save_unknown_spotify_artists(&unknown).await?;
+            updated += artists.len();
+        }
+        Ok(updated)
+    }
+
+    /// Artist links with how they were made, worst matches first so bad links are easy to find.
+    /// Links made before scores were kept come first as those were never scored
+    pub async fn get_artist_link_audit(
+        &self,
+        max_score: Option<f32>,
+        page: i64,
+        per_page: i64,
+    ) -> Result<Vec<ArtistLinkAudit>> {
+        Ok(sqlx::query_as::<Postgres, ArtistLinkAudit>(
+            r#"
+                SELECT artist_links.ann_id, new_artists.names, artist_links.spotify_id,
+                    spotify_artists.name AS spotify_name, artist_links.score, artist_links.created_at
+                FROM artist_links
+                JOIN new_artists ON new_artists.ann_id = artist_links.ann_id
+                LEFT JOIN spotify_artists ON spotify_artists.spotify_id = artist_links.spotify_id
+                WHERE $1::REAL IS NULL OR artist_links.score IS NULL OR artist_links.score <= $1
+                ORDER BY artist_links.score ASC NULLS FIRST, artist_links.ann_id
+                LIMIT $2 OFFSET $3
+                "#,
+        )
+        .bind(max_score)
+        .bind(per_page)
+        // Far off pages are just empty, not an overflow
+        .bind(page.saturating_mul(per_page))
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
@@ -0,0 +1,195 @@
+use super::Database;
+use crate::Result;
+use crate::anilist::types::ReleaseSeason;
+use crate::types::{ListeningStats, NamedCount, SeasonCount, TopAnime, TopArtist, TrackTypeCounts};
+use axum_sessions::async_session::chrono::{DateTime, Utc};
+use sqlx::{FromRow, Postgres, postgres::PgRow};
+
+/// Every (listen, anime entry) pair of a user inside the time window,
+/// a listen counts once per anime even if the song appears in it several times
+const PLAYS_CTE: &str = r#"
+    WITH plays AS (
+        SELECT history.id AS listen_id, animes.*
+        FROM listen_history AS history
+        JOIN animes ON animes.ann_song_id = ANY(history.ann_song_ids)
+        WHERE history.spotify_user_id = $1
+            AND ($2::timestamptz IS NULL OR history.listened_at >= $2)
+            AND ($3::timestamptz IS NULL OR history.listened_at < $3)
+    )
+"#;
+
+#[derive(FromRow)]
+struct DBSeasonCount {
+    year: Option<i32>,
+    season: Option<i16>,
+    plays: i64,
+}
+
+impl Database {
+    /// Runs one query over the plays of a user, $4 is the limit when one is given
+    async fn fetch_stat<T>(
+        &self,
+        query: &str,
+        spotify_user_id: &String,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: Option<i64>,
+    ) -> Result<Vec<T>>
+    where
+        T: for<'r> FromRow<'r, PgRow> + Send + Unpin,
+    {
+        let sql = format!("{}{}", PLAYS_CTE, query);
+        let mut query = sqlx::query_as::<Postgres, T>(&sql)
+            .bind(spotify_user_id)
+            .bind(from)
+            .bind(to);
+        if let Some(limit) = limit {
+            query = query.bind(limit);
+        }
+        Ok(query.fetch_all(&self.pool).await?)
+    }
+
+    /// Statistics over a users listening history, `limit` caps every top list
+    pub async fn get_listening_stats(
+        &self,
+        spotify_user_id: &String,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> Result<ListeningStats> {
+        let total_plays = sqlx::query_scalar::<Postgres, i64>(
+            r#"
+                SELECT COUNT(*)
+                FROM listen_history
+                WHERE spotify_user_id = $1
+                    AND ($2::timestamptz IS NULL OR listened_at >= $2)
+                    AND ($3::timestamptz IS NULL OR listened_at < $3)
+                "#,
+        )
+        .bind(spotify_user_id)
+        .bind(from)
+        .bind(to)
+        .fetch_one(&self.pool)
+        .await?;
+
+        let top_anime = self
+            .fetch_stat::<TopAnime>(
+                r#"
+                SELECT ann_id, MIN(title_eng) AS title, MIN(title_jpn) AS title_japanese,
+                    MIN(cover_image_medium) AS image_url, COUNT(DISTINCT listen_id) AS plays
+                FROM plays
+                GROUP BY ann_id
+                ORDER BY plays DESC
+                LIMIT $4
+                "#,
+                spotify_user_id,
+                from,
+                to,
+                Some(limit),
+            )
+            .await?;
+
+        let top_artists = self
+            .fetch_stat::<TopArtist>(
+                r#"
+                SELECT new_artists.ann_id, new_artists.names, COUNT(DISTINCT plays.listen_id) AS plays
+                FROM plays
+                CROSS JOIN unnest(plays.artists_ann_id) AS artist(ann_id)
+                JOIN new_artists ON new_artists.ann_id = artist.ann_id
+                GROUP BY new_artists.ann_id, new_artists.names
+                ORDER BY plays DESC
+                LIMIT $4
+                "#,
+                spotify_user_id,
+                from,
+                to,
+                Some(limit),
+            )
+            .await?;
+
+        let track_types = self
+            .fetch_stat::<TrackTypeCounts>(
+                r#"
+                SELECT
+                    COUNT(DISTINCT listen_id) FILTER (WHERE track_index_type = 0) AS opening,
+                    COUNT(DISTINCT listen_id) FILTER (WHERE track_index_type = 1) AS insert,
+                    COUNT(DISTINCT listen_id) FILTER (WHERE track_index_type = 2) AS ending
+                FROM plays
+                "#,
+                spotify_user_id,
+                from,
+                to,
+                None,
+            )
+            .await?
+            .pop()
+            .unwrap_or_default();
+
+        let genres = self
+            .fetch_stat::<NamedCount>(
+                r#"
+                SELECT genre AS name, COUNT(DISTINCT listen_id) AS plays
+                FROM plays
+                CROSS JOIN unnest(genres) AS genre
+                GROUP BY genre
+                ORDER BY plays DESC
+                LIMIT $4
+                "#,
+                spotify_user_id,
+                from,
+                to,
+                Some(limit),
+            )
+            .await?;
+
+        let studios = self
+            .fetch_stat::<NamedCount>(
+                r#"
+                SELECT studio AS name, COUNT(DISTINCT listen_id) AS plays
+                FROM plays
+                CROSS JOIN unnest(studio_names) AS studio
+                GROUP BY studio
+                ORDER BY plays DESC
+                LIMIT $4
+                "#,
+                spotify_user_id,
+                from,
+                to,
+                Some(limit),
+            )
+            .await?;
+
+        // Not limited, this is a distribution rather than a top list
+        let seasons = self
+            .fetch_stat::<DBSeasonCount>(
+                r#"
+                SELECT release_year AS year, release_season AS season, COUNT(DISTINCT listen_id) AS plays
+                FROM plays
+                GROUP BY release_year, release_season
+                ORDER BY release_year, release_season
+                "#,
+                spotify_user_id,
+                from,
+                to,
+                None,
+            )
+            .await?
+            .into_iter()
+            .map(|s| SeasonCount {
+                year: s.year,
+                season: s.season.and_then(|s| ReleaseSeason::try_from(s).ok()),
+                plays: s.plays,
+            })
+            .collect();
+
+        Ok(ListeningStats {
+            total_plays,
+            top_anime,
+            top_artists,
+            track_types,
+            genres,
+            studios,
+            seasons,
+        })
+    }
+}
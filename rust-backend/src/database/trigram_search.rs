@@ -0,0 +1,245 @@
+use super::Database;
+use super::databasetypes::{DBAnime, DBArtist};
+use super::regex_search::{artist_name_variants, process_artist_name};
+use crate::Result;
+use crate::japanese_processing::search_form;
+use crate::spotify::responses::TrackObject;
+use crate::types::SongFilters;
+use itertools::Itertools;
+use sqlx::{FromRow, Postgres, QueryBuilder};
+
+#[derive(FromRow)]
+struct ScoredArtist {
+    similarity: f32,
+    #[sqlx(flatten)]
+    artist: DBArtist,
+}
+
+#[derive(FromRow)]
+struct ScoredAnime {
+    similarity: f32,
+    #[sqlx(flatten)]
+    anime: DBAnime,
+}
+
+/// All search forms of an artists names, both as is and with possible (CV: ...) unwrapped
+pub fn artist_search_names(names: &Vec<String>) -> Vec<String> {
+    artist_name_variants(names)
+        .into_iter()
+        .map(|variants| variants.normalized)
+        .unique()
+        .collect()
+}
+
+impl Database {
+    /// pg_trgm similarity (0.0 - 1.0) needed for an artist name to count as the same artist
+    const ARTIST_SIMILARITY_LIMIT: f32 = 0.7;
+    /// pg_trgm similarity (0.0 - 1.0) needed for a song title to be considered a candidate
+    const TITLE_SIMILARITY_LIMIT: f32 = 0.5;
+    const TITLE_SEARCH_LIMIT: i64 = 50;
+
+    pub async fn add_artist_search_names(&self, artists: Vec<(i32, &Vec<String>)>) -> Result<()> {
+        let search_names: Vec<(i32, String)> = artists
+            .into_iter()
+            .flat_map(|(ann_id, names)| {
+                artist_search_names(names)
+                    .into_iter()
+                    .map(move |name| (ann_id, name))
+            })
+            .collect();
+
+        if search_names.is_empty() {
+            return Ok(());
+        }
+
+        let mut query_builder: QueryBuilder<Postgres> =
+            QueryBuilder::new("INSERT INTO artist_search_names (ann_id, search_name) ");
+
+        query_builder.push_values(search_names, |mut builder, (ann_id, name)| {
+            builder.push_bind(ann_id).push_bind(name);
+        });
+
+        query_builder.push(" ON CONFLICT DO NOTHING");
+
+        query_builder.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    pub async fn add_anime_search_titles(&self, animes: &Vec<&DBAnime>) -> Result<()> {
+        let search_titles: Vec<(i32, String)> = animes
+            .iter()
+            .flat_map(|anime| {
+                let ann_id = anime.ann_id;
+                anime
+                    .titles()
+                    .into_iter()
+                    .map(move |title| (ann_id, search_form(title)))
+            })
+            .filter(|(_, title)| !title.is_empty())
+            .unique()
+            .collect();
+
+        if search_titles.is_empty() {
+            return Ok(());
+        }
+
+        let mut query_builder: QueryBuilder<Postgres> =
+            QueryBuilder::new("INSERT INTO anime_title_search (ann_id, search_title) ");
+
+        query_builder.push_values(search_titles, |mut builder, (ann_id, title)| {
+            builder.push_bind(ann_id).push_bind(title);
+        });
+
+        query_builder.push(" ON CONFLICT DO NOTHING");
+
+        query_builder.build().execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Finds animes with any title (english, japanese, romaji or alternative) like the query, one song per anime.
+    /// Uses word similarity so partial titles and abbreviations still find the anime.
+    pub async fn search_animes_by_title(
+        &self,
+        query: &str,
+        min_similarity: f32,
+        filters: &SongFilters,
+        limit: i64,
+    ) -> Result<Vec<(DBAnime, f32)>> {
+        // Animes only show up if at least one of their songs isn't hidden
+        let animes = sqlx::query_as::<Postgres, ScoredAnime>(
+            r#"
+                SELECT anime.*, best.similarity
+                FROM (
+                    SELECT ann_id,
+                        MAX(word_similarity($1, search_title)) AS similarity,
+                        MAX(similarity($1, search_title)) AS full_similarity
+                    FROM anime_title_search
+                    WHERE $1 <% search_title
+                    GROUP BY ann_id
+                ) AS best
+                CROSS JOIN LATERAL (
+                    SELECT * FROM animes
+                    WHERE animes.ann_id = best.ann_id
+                        AND NOT ($4 AND is_dub IS TRUE)
+                        AND NOT ($5 AND is_rebroadcast IS TRUE)
+                        AND (song_category IS NULL OR NOT song_category = ANY($6))
+                    LIMIT 1
+                ) AS anime
+                WHERE best.similarity >= $2
+                ORDER BY best.similarity DESC, best.full_similarity DESC
+                LIMIT $3
+                "#,
+        )
+        .bind(search_form(query))
+        .bind(min_similarity)
+        .bind(limit)
+        .bind(filters.hide_dubs)
+        .bind(filters.hide_rebroadcasts)
+        .bind(filters.hidden_categories())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(animes
+            .into_iter()
+            .map(|a| (a.anime, a.similarity))
+            .collect())
+    }
+
+    /// Finds artists with a name similar to any of the given names, best match first.
+    pub async fn search_artists_by_name(
+        &self,
+        names: Vec<&String>,
+        min_similarity: f32,
+    ) -> Result<Vec<(DBArtist, f32)>> {
+        let search_names: Vec<String> = names
+            .iter()
+            .map(|name| search_form(&process_artist_name(name)))
+            .collect();
+
+        // unnest + join rather than ANY() so each name can use the trigram index
+        let artists = sqlx::query_as::<Postgres, ScoredArtist>(
+            r#"
+                SELECT new_artists.*, best.similarity
+                FROM (
+                    SELECT search.ann_id, MAX(similarity(search.search_name, query.name)) AS similarity
+                    FROM unnest($1::text[]) AS query(name)
+                    JOIN artist_search_names AS search ON search.search_name % query.name
+                    GROUP BY search.ann_id
+                ) AS best
+                JOIN new_artists ON new_artists.ann_id = best.ann_id
+                WHERE best.similarity >= $2
+                ORDER BY best.similarity DESC
+                "#,
+        )
+        .bind(&search_names)
+        .bind(min_similarity)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(artists
+            .into_iter()
+            .map(|a| (a.artist, a.similarity))
+            .collect())
+    }
+
+    /// Finds animes with a song title similar to the given title, best match first.
+    pub async fn search_animes_by_song_title(
+        &self,
+        title: &str,
+        min_similarity: f32,
+        limit: i64,
+    ) -> Result<Vec<(DBAnime, f32)>> {
+        let animes = sqlx::query_as::<Postgres, ScoredAnime>(
+            r#"
+                SELECT *, similarity(song_name_normalized, $1) AS similarity
+                FROM animes
+                WHERE song_name_normalized % $1 AND similarity(song_name_normalized, $1) >= $2
+                ORDER BY similarity DESC
+                LIMIT $3
+                "#,
+        )
+        .bind(search_form(title))
+        .bind(min_similarity)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(animes
+            .into_iter()
+            .map(|a| (a.anime, a.similarity))
+            .collect())
+    }
+
+    /// Tries to figure out the ann ids of the artists of a track using only our own database,
+    /// first by artist name and then by song title with the artists used as a tie breaker.
+    pub async fn search_artist_ids_for_track(&self, track: &TrackObject) -> Result<Vec<i32>> {
+        let artist_names: Vec<&String> = track.artists.iter().map(|a| &a.name).collect();
+
+        let artists = self
+            .search_artists_by_name(artist_names.clone(), Self::ARTIST_SIMILARITY_LIMIT)
+            .await?;
+
+        if !artists.is_empty() {
+            return Ok(artists.iter().map(|a| a.0.ann_id).unique().collect());
+        }
+
+        let mut candidates: Vec<DBAnime> = self
+            .search_animes_by_song_title(
+                &track.name,
+                Self::TITLE_SIMILARITY_LIMIT,
+                Self::TITLE_SEARCH_LIMIT,
+            )
+            .await?
+            .into_iter()
+            .map(|a| a.0)
+            .collect();
+
+        let (best, certainty) = DBAnime::pick_best_by_artist_names(&mut candidates, artist_names)?;
+
+        if certainty > Self::ACCURACY_AUTOADD_LIMIT {
+            Ok(best[0].artists_ann_id.clone())
+        } else {
+            Ok(vec![])
+        }
+    }
+}
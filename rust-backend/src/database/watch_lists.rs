@@ -0,0 +1,75 @@
+use super::Database;
+use crate::Result;
+use crate::types::{ListSource, WatchList, WatchStatus};
+use sqlx::{FromRow, Postgres, QueryBuilder};
+
+#[derive(FromRow)]
+struct DBWatchListEntry {
+    anilist_id: Option<i32>,
+    mal_id: Option<i32>,
+    status: WatchStatus,
+}
+
+impl Database {
+    /// Replaces everything we have from one of a users lists,
+    /// entries are (anilist id, mal id, status)
+    pub async fn replace_watch_list(
+        &self,
+        spotify_user_id: &String,
+        source: ListSource,
+        entries: &Vec<(Option<i32>, Option<i32>, WatchStatus)>,
+    ) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM watch_list_entries WHERE spotify_user_id = $1 AND source = $2")
+            .bind(spotify_user_id)
+            .bind(source)
+            .execute(&mut *transaction)
+            .await?;
+
+        // Postgres allows at most 65535 binds per statement
+        for chunk in entries.chunks(10000) {
+            let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+                "INSERT INTO watch_list_entries (spotify_user_id, source, anilist_id, mal_id, status) ",
+            );
+            query_builder.push_values(chunk, |mut builder, (anilist_id, mal_id, status)| {
+                builder
+                    .push_bind(spotify_user_id)
+                    .push_bind(source)
+                    .push_bind(anilist_id)
+                    .push_bind(mal_id)
+                    .push_bind(status);
+            });
+            query_builder.build().execute(&mut *transaction).await?;
+        }
+
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_watch_list(&self, spotify_user_id: &String) -> Result<WatchList> {
+        // AniList last so it wins when both lists have the anime
+        let entries = sqlx::query_as::<Postgres, DBWatchListEntry>(
+            r#"
+                SELECT anilist_id, mal_id, status
+                FROM watch_list_entries
+                WHERE spotify_user_id = $1
+                ORDER BY source DESC
+                "#,
+        )
+        .bind(spotify_user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut watch_list = WatchList::default();
+        for entry in entries {
+            if let Some(id) = entry.anilist_id {
+                watch_list.by_anilist.insert(id, entry.status);
+            }
+            if let Some(id) = entry.mal_id {
+                watch_list.by_mal.insert(id, entry.status);
+            }
+        }
+        Ok(watch_list)
+    }
+}
@@ -0,0 +1,363 @@
+use std::{future::Future, sync::Arc};
+
+use log::warn;
+use tokio::task::{JoinError, JoinSet};
+
+use super::{
+    commands::Command,
+    embed::Reply,
+    gateway::{Gateway, IncomingMessage},
+};
+use crate::{
+    AppState, Error, Result,
+    auth::{api_token_access_token, hash_token},
+    database::Database,
+    spotify::{
+        api::{currently_playing, get_song},
+        responses::{CurrentlyPlayingResponses, Item},
+    },
+    types::{ApiScope, NewSong},
+};
+
+const HELP: &str = "Post a spotify track link and I'll tell you which anime it is from.\n\
+    `!link <token>` in a DM links your account using an API token with the identify scope, \
+    after that `!np` shows what you are playing and `!np @someone` what they are playing.\n\
+    `!unlink` forgets your token.";
+const NOT_LINKED: &str = "That needs a linked account, DM me `!help` to see how.";
+// Lookups run side by side so a slow one doesn't hold up everyone, but not so many they hog the database pool
+const MAX_RUNNING_COMMANDS: usize = 8;
+
+/// Everything the bot needs from the rest of the backend
+pub trait Lookup {
+    /// Matches a track, no linked account needed for this
+    fn track(&self, spotify_id: &String) -> impl Future<Output = Result<NewSong>> + Send;
+    /// Matches what a linked user is playing, None when they are not playing anything
+    fn now_playing(
+        &self,
+        discord_user_id: u64,
+    ) -> impl Future<Output = Result<Option<NewSong>>> + Send;
+    fn link(&self, discord_user_id: u64, token: &String)
+    -> impl Future<Output = Result<()>> + Send;
+    fn unlink(&self, discord_user_id: u64) -> impl Future<Output = Result<bool>> + Send;
+}
+
+pub struct SpotifyLookup {
+    app_state: Arc<AppState>,
+}
+
+impl SpotifyLookup {
+    pub fn new(app_state: Arc<AppState>) -> Self {
+        Self { app_state }
+    }
+
+    async fn access_token(&self, discord_user_id: u64) -> Result<String> {
+        let api_token = self
+            .app_state
+            .database
+            .get_discord_link(discord_user_id as i64)
+            .await?
+            .ok_or(Error::BadOAuth)?;
+        api_token_access_token(&self.app_state, api_token).await
+    }
+}
+
+impl Lookup for SpotifyLookup {
+    async fn track(&self, spotify_id: &String) -> Result<NewSong> {
+        let token = self.app_state.client_token.access_token().await?;
+        let song = get_song(spotify_id.clone(), token).await?;
+        self.app_state
+            .database
+            .get_anime_2(&song, &self.app_state.anisong_db, Database::ACCURACY_CUTOFF)
+            .await
+    }
+
+    async fn now_playing(&self, discord_user_id: u64) -> Result<Option<NewSong>> {
+        let token = self.access_token(discord_user_id).await?;
+        let song = match currently_playing(&token).await? {
+            CurrentlyPlayingResponses::Playing(playing) => match playing.item {
+                Item::TrackObject(song) => song,
+                _ => return Err(Error::NotASong),
+            },
+            CurrentlyPlayingResponses::BadToken | CurrentlyPlayingResponses::BadOAuth => {
+                return Err(Error::BadOAuth);
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(
+            self.app_state
+                .database
+                .get_anime_2(&song, &self.app_state.anisong_db, Database::ACCURACY_CUTOFF)
+                .await?,
+        ))
+    }
+
+    async fn link(&self, discord_user_id: u64, token: &String) -> Result<()> {
+        let api_token = self
+            .app_state
+            .database
+            .use_api_token(&hash_token(token))
+            .await?
+            .ok_or(Error::BadOAuth)?;
+        if !api_token.allows(ApiScope::Identify) {
+            return Err(Error::MissingScope(ApiScope::Identify.as_str().to_string()));
+        }
+
+        self.app_state
+            .database
+            .link_discord_user(discord_user_id as i64, api_token.id)
+            .await
+    }
+
+    async fn unlink(&self, discord_user_id: u64) -> Result<bool> {
+        self.app_state
+            .database
+            .unlink_discord_user(discord_user_id as i64)
+            .await
+    }
+}
+
+pub struct Bot<G: Gateway, L: Lookup> {
+    gateway: G,
+    lookup: L,
+}
+
+impl<G: Gateway, L: Lookup + Send + Sync + 'static> Bot<G, L> {
+    pub fn new(gateway: G, lookup: L) -> Self {
+        Self { gateway, lookup }
+    }
+
+    /// Answers messages until the gateway closes. Every command runs as its own task,
+    /// a panicking lookup only loses that one answer
+    pub async fn run(self) {
+        let Self {
+            mut gateway,
+            lookup,
+        } = self;
+        let lookup = Arc::new(lookup);
+        let mut running: JoinSet<(u64, Reply)> = JoinSet::new();
+
+        loop {
+            tokio::select! {
+                message = gateway.next_message(), if running.len() < MAX_RUNNING_COMMANDS => {
+                    let Some(message) = message else {
+                        break;
+                    };
+                    let Some(command) = Command::parse(&message.content, message.author_id) else {
+                        continue;
+                    };
+                    let lookup = lookup.clone();
+                    running.spawn(async move {
+                        let reply = respond(&*lookup, command, &message).await;
+                        (message.channel_id, reply)
+                    });
+                }
+                Some(finished) = running.join_next() => send_reply(&gateway, finished).await,
+            }
+        }
+
+        // The commands that were already running still get their answer
+        while let Some(finished) = running.join_next().await {
+            send_reply(&gateway, finished).await;
+        }
+    }
+}
+
+async fn send_reply<G: Gateway>(
+    gateway: &G,
+    finished: std::result::Result<(u64, Reply), JoinError>,
+) {
+    match finished {
+        Ok((channel_id, reply)) => {
+            if let Err(error) = gateway.send(channel_id, reply).await {
+                warn!("Failed to answer discord message: {:?}", error);
+            }
+        }
+        Err(error) => warn!("Discord command failed: {:?}", error),
+    }
+}
+
+async fn respond<L: Lookup>(lookup: &L, command: Command, message: &IncomingMessage) -> Reply {
+    match command {
+        Command::Track(spotify_id) => match lookup.track(&spotify_id).await {
+            Ok(new_song) => Reply::from_new_song(&new_song),
+            Err(error) => error_reply(error),
+        },
+        Command::NowPlaying(discord_user_id) => match lookup.now_playing(discord_user_id).await {
+            Ok(Some(new_song)) => Reply::from_new_song(&new_song),
+            Ok(None) => Reply::text("Nothing is playing right now."),
+            Err(error) => error_reply(error),
+        },
+        // Anyone in the channel could use a token posted there
+        Command::Link(_) if !message.is_direct => Reply::text(
+            "Send `!link` in a DM instead, and revoke that token since everyone here can see it.",
+        ),
+        Command::Link(token) => match lookup.link(message.author_id, &token).await {
+            Ok(()) => Reply::text("Linked, `!np` now shows what you are playing."),
+            Err(Error::BadOAuth) => Reply::text("That token doesn't exist or was revoked."),
+            Err(Error::MissingScope(scope)) => {
+                Reply::text(format!("That token needs the {} scope.", scope))
+            }
+            Err(error) => error_reply(error),
+        },
+        Command::Unlink => match lookup.unlink(message.author_id).await {
+            Ok(true) => Reply::text("Unlinked."),
+            Ok(false) => Reply::text("You weren't linked."),
+            Err(error) => error_reply(error),
+        },
+        Command::Help => Reply::text(HELP),
+    }
+}
+
+fn error_reply(error: Error) -> Reply {
+    match error {
+        Error::BadOAuth => Reply::text(NOT_LINKED),
+        Error::NotASong => Reply::text("That isn't a song."),
+        error => {
+            warn!("Discord lookup failed: {:?}", error);
+            Reply::text("Something went wrong looking that up.")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{SongInfo, SongMiss};
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    struct MockGateway {
+        incoming: VecDeque<IncomingMessage>,
+        sent: Arc<Mutex<Vec<(u64, Reply)>>>,
+    }
+
+    impl Gateway for MockGateway {
+        async fn next_message(&mut self) -> Option<IncomingMessage> {
+            self.incoming.pop_front()
+        }
+
+        async fn send(&self, channel_id: u64, reply: Reply) -> Result<()> {
+            self.sent.lock().unwrap().push((channel_id, reply));
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct MockLookup {
+        linked: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl Lookup for MockLookup {
+        async fn track(&self, spotify_id: &String) -> Result<NewSong> {
+            Ok(NewSong::Miss(SongMiss {
+                song_info: SongInfo {
+                    title: "Unravel".to_string(),
+                    artists: vec!["TK".to_string()],
+                    album_picture_url: String::new(),
+                    spotify_id: spotify_id.clone(),
+                },
+                possible_anime: vec![],
+            }))
+        }
+
+        async fn now_playing(&self, discord_user_id: u64) -> Result<Option<NewSong>> {
+            match self.linked.lock().unwrap().contains(&discord_user_id) {
+                true => Ok(None),
+                false => Err(Error::BadOAuth),
+            }
+        }
+
+        async fn link(&self, discord_user_id: u64, _token: &String) -> Result<()> {
+            self.linked.lock().unwrap().push(discord_user_id);
+            Ok(())
+        }
+
+        async fn unlink(&self, discord_user_id: u64) -> Result<bool> {
+            let mut linked = self.linked.lock().unwrap();
+            let was_linked = linked.contains(&discord_user_id);
+            linked.retain(|id| *id != discord_user_id);
+            Ok(was_linked)
+        }
+    }
+
+    fn message(channel_id: u64, author_id: u64, content: &str, is_direct: bool) -> IncomingMessage {
+        IncomingMessage {
+            channel_id,
+            author_id,
+            content: content.to_string(),
+            is_direct,
+        }
+    }
+
+    async fn run(messages: Vec<IncomingMessage>, lookup: MockLookup) -> Vec<(u64, Reply)> {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let gateway = MockGateway {
+            incoming: messages.into(),
+            sent: sent.clone(),
+        };
+        Bot::new(gateway, lookup).run().await;
+        sent.lock().unwrap().drain(..).collect()
+    }
+
+    #[tokio::test]
+    async fn answers_track_links_and_ignores_chatter() {
+        let sent = run(
+            vec![
+                message(1, 10, "hello", false),
+                message(
+                    1,
+                    10,
+                    "https://open.spotify.com/track/2QjOHCTQ1Jl3zawyYOpxh6",
+                    false,
+                ),
+            ],
+            MockLookup::default(),
+        )
+        .await;
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, 1);
+        assert!(sent[0].1.content.contains("Unravel"));
+    }
+
+    #[tokio::test]
+    async fn only_links_in_direct_messages() {
+        // Commands of one run answer in any order, so each step gets its own run
+        let lookup = MockLookup::default();
+        let linked = lookup.linked.clone();
+        let sent = run(
+            vec![
+                message(1, 10, "!link wa_secret", false),
+                message(2, 10, "!np", true),
+            ],
+            lookup,
+        )
+        .await;
+        assert_eq!(sent.len(), 2);
+        assert!(
+            sent.iter()
+                .any(|(channel_id, reply)| *channel_id == 1 && reply.content.contains("DM"))
+        );
+        assert!(
+            sent.iter()
+                .any(|(channel_id, reply)| *channel_id == 2 && reply.content == NOT_LINKED)
+        );
+        assert!(linked.lock().unwrap().is_empty());
+
+        let lookup = MockLookup {
+            linked: linked.clone(),
+        };
+        run(vec![message(2, 10, "!link wa_secret", true)], lookup).await;
+        assert_eq!(*linked.lock().unwrap(), vec![10]);
+
+        let lookup = MockLookup {
+            linked: linked.clone(),
+        };
+        let sent = run(vec![message(2, 10, "!np", true)], lookup).await;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].1.content, "Nothing is playing right now.");
+    }
+}
@@ -0,0 +1,81 @@
+use crate::spotify::links::SpotifyLink;
+
+/// What a discord message asks the bot to do
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    // A spotify track link anywhere in the message
+    Track(String),
+    // What a linked user is playing, the author when nobody is mentioned
+    NowPlaying(u64),
+    Link(String),
+    Unlink,
+    Help,
+}
+
+impl Command {
+    pub fn parse(content: &str, author_id: u64) -> Option<Self> {
+        let mut words = content.split_whitespace();
+        match words.next()? {
+            "!np" => Some(Self::NowPlaying(
+                words.next().and_then(parse_mention).unwrap_or(author_id),
+            )),
+            "!link" => Some(match words.next() {
+                Some(token) => Self::Link(token.to_string()),
+                None => Self::Help,
+            }),
+            "!unlink" => Some(Self::Unlink),
+            "!anime" | "!help" => Some(Self::Help),
+            _ => content
+                .split_whitespace()
+                .find_map(|word| match SpotifyLink::parse(word) {
+                    Some(SpotifyLink::Track(id)) => Some(Self::Track(id)),
+                    _ => None,
+                }),
+        }
+    }
+}
+
+/// User mentions look like <@123> or <@!123> for nicknames
+fn parse_mention(word: &str) -> Option<u64> {
+    word.strip_prefix("<@")?
+        .strip_suffix('>')?
+        .trim_start_matches('!')
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_track_links_in_messages() {
+        assert_eq!(
+            Command::parse(
+                "this one is so good https://open.spotify.com/track/2QjOHCTQ1Jl3zawyYOpxh6?si=x",
+                1
+            ),
+            Some(Command::Track("2QjOHCTQ1Jl3zawyYOpxh6".to_string()))
+        );
+        assert_eq!(
+            Command::parse("https://open.spotify.com/album/4yP0hdKOZPNshxUOjY0cZj", 1),
+            None
+        );
+        assert_eq!(Command::parse("just talking", 1), None);
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(Command::parse("!np", 1), Some(Command::NowPlaying(1)));
+        assert_eq!(
+            Command::parse("!np <@!42>", 1),
+            Some(Command::NowPlaying(42))
+        );
+        assert_eq!(
+            Command::parse("!link wa_abc", 1),
+            Some(Command::Link("wa_abc".to_string()))
+        );
+        assert_eq!(Command::parse("!link", 1), Some(Command::Help));
+        assert_eq!(Command::parse("!unlink", 1), Some(Command::Unlink));
+    }
+}
@@ -0,0 +1,132 @@
+use twilight_model::channel::message::Embed;
+use twilight_util::builder::embed::{
+    EmbedBuilder, EmbedFieldBuilder, EmbedFooterBuilder, ImageSource,
+};
+
+use crate::types::{AnimeTrackIndex, FrontendAnimeEntry, NewSong, SongInfo};
+
+// Discord allows 10 embeds per message, more than a few drowns out the chat
+const MAX_EMBEDS: usize = 3;
+
+/// One anime a song is from, as it is shown in discord
+#[derive(Debug)]
+pub struct AnimeEmbed {
+    pub title: String,
+    pub url: Option<String>,
+    pub description: String,
+    pub thumbnail: Option<String>,
+    pub links: Vec<(String, String)>,
+    pub footer: String,
+}
+
+impl AnimeEmbed {
+    pub fn new(entry: &FrontendAnimeEntry, footer: String) -> Self {
+        let mut links = Vec::new();
+        if let Some(anilist_id) = entry.linked_ids.anilist {
+            links.push((
+                "AniList".to_string(),
+                format!("https://anilist.co/anime/{}", anilist_id.0),
+            ));
+        }
+        if let Some(mal_id) = entry.linked_ids.myanimelist {
+            links.push((
+                "MyAnimeList".to_string(),
+                format!("https://myanimelist.net/anime/{}", mal_id),
+            ));
+        }
+
+        Self {
+            title: entry.title.clone(),
+            url: links.first().map(|(_, url)| url.clone()),
+            description: format!(
+                "{} · {} by {}",
+                track_index_name(&entry.track_index),
+                entry.song_name,
+                entry.artist_names.join(", ")
+            ),
+            thumbnail: entry.image_url.as_ref().map(|url| url.as_str().to_string()),
+            links,
+            footer,
+        }
+    }
+
+    pub fn to_embed(&self) -> Embed {
+        let mut builder = EmbedBuilder::new()
+            .title(&self.title)
+            .description(&self.description)
+            .footer(EmbedFooterBuilder::new(&self.footer));
+        if let Some(url) = &self.url {
+            builder = builder.url(url);
+        }
+        if let Some(source) = self
+            .thumbnail
+            .as_ref()
+            .and_then(|url| ImageSource::url(url).ok())
+        {
+            builder = builder.thumbnail(source);
+        }
+        for (name, url) in &self.links {
+            builder = builder.field(EmbedFieldBuilder::new(name, url).inline());
+        }
+        builder.build()
+    }
+}
+
+fn track_index_name(track_index: &AnimeTrackIndex) -> String {
+    match track_index {
+        AnimeTrackIndex::Opening(number) => format!("Opening {}", number),
+        AnimeTrackIndex::Insert(_) => "Insert Song".to_string(),
+        AnimeTrackIndex::Ending(number) => format!("Ending {}", number),
+    }
+}
+
+/// A message for the bot to send
+#[derive(Debug)]
+pub struct Reply {
+    pub content: String,
+    pub embeds: Vec<AnimeEmbed>,
+}
+
+impl Reply {
+    pub fn text(content: impl Into<String>) -> Self {
+        Self {
+            content: content.into(),
+            embeds: vec![],
+        }
+    }
+
+    pub fn from_new_song(new_song: &NewSong) -> Self {
+        match new_song {
+            NewSong::Hit(hit) => Self {
+                content: format!("{} is from", song_name(&hit.song_info)),
+                embeds: hit
+                    .anime_info
+                    .iter()
+                    .take(MAX_EMBEDS)
+                    .map(|entry| AnimeEmbed::new(entry, format!("{}% match", hit.certainty)))
+                    .collect(),
+            },
+            NewSong::Miss(miss) if miss.possible_anime.is_empty() => Self::text(format!(
+                "Couldn't find any anime with {}",
+                song_name(&miss.song_info)
+            )),
+            NewSong::Miss(miss) => Self {
+                content: format!("{} might be from", song_name(&miss.song_info)),
+                embeds: miss
+                    .possible_anime
+                    .iter()
+                    .take(MAX_EMBEDS)
+                    .map(|entry| AnimeEmbed::new(entry, "Possible match".to_string()))
+                    .collect(),
+            },
+        }
+    }
+}
+
+fn song_name(song_info: &SongInfo) -> String {
+    format!(
+        "**{}** by {}",
+        song_info.title,
+        song_info.artists.join(", ")
+    )
+}
@@ -0,0 +1,79 @@
+use std::future::Future;
+
+use log::warn;
+use twilight_gateway::{Event, EventTypeFlags, Intents, Shard, ShardId, StreamExt};
+use twilight_http::Client;
+use twilight_model::{
+    channel::message::{AllowedMentions, Embed},
+    id::Id,
+};
+
+use super::embed::Reply;
+use crate::Result;
+
+pub struct IncomingMessage {
+    pub channel_id: u64,
+    pub author_id: u64,
+    pub content: String,
+    // Sent in a DM rather than a server channel
+    pub is_direct: bool,
+}
+
+/// The connection to discord, a mock one stands in for it in tests
+pub trait Gateway {
+    /// The next message someone sent, None once the connection is gone for good.
+    /// Has to be cancel safe, the bot stops waiting for it whenever an answer is ready to send
+    fn next_message(&mut self) -> impl Future<Output = Option<IncomingMessage>> + Send;
+    /// Only awaited on the task running the bot, so this doesn't have to be Send, which a shard isn't
+    fn send(&self, channel_id: u64, reply: Reply) -> impl Future<Output = Result<()>>;
+}
+
+pub struct TwilightGateway {
+    shard: Shard,
+    http: Client,
+}
+
+impl TwilightGateway {
+    pub fn new(token: String) -> Self {
+        let intents = Intents::GUILD_MESSAGES | Intents::DIRECT_MESSAGES | Intents::MESSAGE_CONTENT;
+        Self {
+            shard: Shard::new(ShardId::ONE, token.clone(), intents),
+            http: Client::new(token),
+        }
+    }
+}
+
+impl Gateway for TwilightGateway {
+    async fn next_message(&mut self) -> Option<IncomingMessage> {
+        loop {
+            match self
+                .shard
+                .next_event(EventTypeFlags::MESSAGE_CREATE)
+                .await?
+            {
+                Ok(Event::MessageCreate(message)) if !message.author.bot => {
+                    return Some(IncomingMessage {
+                        channel_id: message.channel_id.get(),
+                        author_id: message.author.id.get(),
+                        content: message.content.clone(),
+                        is_direct: message.guild_id.is_none(),
+                    });
+                }
+                Ok(_) => {}
+                Err(error) => warn!("Discord gateway error: {:?}", error),
+            }
+        }
+    }
+
+    async fn send(&self, channel_id: u64, reply: Reply) -> Result<()> {
+        let embeds: Vec<Embed> = reply.embeds.iter().map(|e| e.to_embed()).collect();
+        // Replies quote anime and song titles, none of which should be able to ping anyone
+        self.http
+            .create_message(Id::new(channel_id))
+            .allowed_mentions(Some(&AllowedMentions::default()))
+            .content(&reply.content)
+            .embeds(&embeds)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,6 @@
+pub mod bot;
+pub mod commands;
+pub mod embed;
+pub mod gateway;
+
+pub use bot::{Bot, Lookup, SpotifyLookup};
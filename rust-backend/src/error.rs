@@ -7,7 +7,15 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 impl IntoResponse for Error {
     fn into_response(self) -> axum::response::Response {
-        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND.into_response(),
+            Self::BadOAuth => StatusCode::UNAUTHORIZED.into_response(),
+            Self::InvalidParameter(reason) => (StatusCode::BAD_REQUEST, reason).into_response(),
+            Self::MissingScope(scope) => (StatusCode::FORBIDDEN, scope).into_response(),
+            Self::Conflict(reason) => (StatusCode::CONFLICT, reason).into_response(),
+            Self::TooManyRequests => StatusCode::TOO_MANY_REQUESTS.into_response(),
+            _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        }
     }
 }
 #[derive(Debug)]
@@ -15,6 +23,11 @@ pub enum Error {
     BadOAuth,
     NotASong,
     NotImplemented,
+    NotFound,
+    MissingScope(String),
+    InvalidParameter(String),
+    Conflict(String),
+    TooManyRequests,
     BadRequest {
         url: String,
         status_code: axum::http::StatusCode,
@@ -24,6 +37,7 @@ pub enum Error {
     ParseError(String),
     SqlxError(sqlx::Error),
     MigrateError(MigrateError),
+    DiscordError(twilight_http::Error),
     // SessionError(tower_sessions_core::session::Error)
 }
 
@@ -33,6 +47,12 @@ impl From<MigrateError> for Error {
     }
 }
 
+impl From<twilight_http::Error> for Error {
+    fn from(value: twilight_http::Error) -> Self {
+        Self::DiscordError(value)
+    }
+}
+
 impl From<sqlx::Error> for Error {
     fn from(value: sqlx::Error) -> Self {
         Self::SqlxError(value)
@@ -1,7 +1,12 @@
 use fuzzywuzzy::fuzz;
 use kakasi::{self, IsJapanese};
+use lazy_static::lazy_static;
 use regex::Regex;
 
+lazy_static! {
+    static ref JAPANESE_REGEX: Regex = Regex::new(r"[\p{Hiragana}\p{Katakana}\p{Han}]").unwrap();
+}
+
 pub fn process_possible_japanese(japanese: &str) -> String {
     if kakasi::is_japanese(japanese) == IsJapanese::False {
         japanese.to_string()
@@ -17,6 +22,33 @@ pub fn normalize_text(text: &str) -> String {
     return deunicode::deunicode(&new_text);
 }
 
+/// The romanized and normalized form of a text, this is what gets stored in the search columns
+pub fn search_form(text: &str) -> String {
+    normalize_text(&process_possible_japanese(text))
+}
+
+/// Romanized and normalized forms of a text, stored in the database so scoring candidates doesn't need kakasi
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextVariants {
+    pub romaji: String,
+    pub normalized: String,
+}
+
+impl TextVariants {
+    pub fn new(text: &str) -> Self {
+        let romaji = process_possible_japanese(text);
+        Self {
+            normalized: normalize_text(&romaji),
+            romaji,
+        }
+    }
+}
+
+/// Same score as process_similarity but for texts that are already in their search_form
+pub fn normalized_similarity(normalized_a: &str, normalized_b: &str) -> f32 {
+    fuzz::ratio(normalized_a, normalized_b) as f32
+}
+
 #[allow(dead_code)]
 fn remove_vowels(word: &str) -> String {
     word.chars()
@@ -29,8 +61,7 @@ fn remove_consonants(word: &str) -> String {
 }
 
 pub fn process_similarity(japanese_text: &str, romaji_text: &str) -> f32 {
-    let japanese_regex = Regex::new(r"[\p{Hiragana}\p{Katakana}\p{Han}]").unwrap();
-    if japanese_regex.is_match(japanese_text) {
+    if JAPANESE_REGEX.is_match(japanese_text) {
         let romanized_japanese = process_possible_japanese(japanese_text);
         let normalized_japanese = normalize_text(&romanized_japanese);
         let normalized_romaji = normalize_text(romaji_text);
@@ -0,0 +1,76 @@
+pub mod anilist;
+pub mod anisong;
+pub mod auth;
+pub mod database;
+pub mod discord;
+pub mod error;
+pub mod japanese_processing;
+pub mod myanimelist;
+pub mod routes;
+pub mod spotify;
+pub mod types;
+
+use anisong::AnisongClient;
+use database::Database;
+pub use error::{Error, Result};
+use spotify::api::{ClientTokenManager, SessionRefreshes};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+use tokio::sync::Semaphore;
+
+// Each track lookup can hold a database connection and wait on anisong, so anonymous ones get a share of the pool
+const MAX_PUBLIC_TRACK_LOOKUPS: usize = 4;
+
+pub struct AppState {
+    client_id: String,
+    client_secret: String,
+    // ip: String,
+    redirect_uri: String,
+    anisong_db: AnisongClient,
+    database: Database,
+    // For spotify lookups that aren't done for a specific user
+    client_token: ClientTokenManager,
+    // Spotify access tokens made for API tokens, by API token id with their expire time
+    api_token_cache: Mutex<HashMap<i32, (String, u64)>>,
+    // So concurrent requests of one session refresh its spotify token only once
+    session_refreshes: SessionRefreshes,
+    // Lookups running for /api/track, which anyone can call
+    public_track_lookups: Semaphore,
+}
+
+impl AppState {
+    pub async fn load() -> Self {
+        // let ip = env::var("ip").unwrap();
+        let database = Database::new().await;
+        database.run_migrations().await.unwrap();
+        let client_id = env::var("ClientID").unwrap();
+        let client_secret = env::var("ClientSecret").unwrap();
+        return Self {
+            client_token: ClientTokenManager::new(client_id.clone(), client_secret.clone()),
+            client_id,
+            client_secret,
+            redirect_uri: format!("http://whatanime.ddns.net:8000/callback"),
+            // ip,
+            anisong_db: AnisongClient::new(),
+            database,
+            api_token_cache: Mutex::new(HashMap::new()),
+            session_refreshes: SessionRefreshes::default(),
+            public_track_lookups: Semaphore::new(MAX_PUBLIC_TRACK_LOOKUPS),
+        };
+    }
+
+    /// Marks the imports of a previous server run as interrupted, only the server runs imports
+    /// so only it may call this
+    pub async fn interrupt_running_imports(&self) -> Result<()> {
+        self.database.interrupt_running_imports().await
+    }
+
+    /// Fills in spotify artist details for new artist links, a batch at a time to stay under spotifys rate limit
+    pub async fn enrich_spotify_artists(&self) -> Result<usize> {
+        const MAX_ARTISTS_PER_RUN: i64 = 500;
+        self.database
+            .enrich_spotify_artists(&self.client_token, MAX_ARTISTS_PER_RUN)
+            .await
+    }
+}
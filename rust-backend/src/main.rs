@@ -1,22 +1,12 @@
-mod anilist;
-mod anisong;
-mod database;
-mod error;
-mod japanese_processing;
-mod routes;
-mod spotify;
-mod types;
-
-use anisong::AnisongClient;
 use axum::http::Method;
 use axum::http::header::{ACCEPT, AUTHORIZATION};
-use axum::routing::post;
+use axum::routing::{delete, post};
 use axum::{Router, http::HeaderValue, routing::get};
-use database::Database;
+use backend::AppState;
+use backend::database::Database;
 use dotenv::dotenv;
 use env_logger::Target;
-pub use error::{Error, Result};
-use log::info;
+use log::{info, warn};
 use std::time::Duration;
 use std::{env, sync::Arc};
 use tokio::task;
@@ -24,32 +14,12 @@ use tokio::time::interval;
 use tower_http::cors::CorsLayer;
 use tower_sessions::{MemoryStore, SessionManagerLayer, cookie::SameSite};
 
-use routes::{callback, confirm_anime, login, report, update};
-
-struct AppState {
-    client_id: String,
-    client_secret: String,
-    // ip: String,
-    redirect_uri: String,
-    anisong_db: AnisongClient,
-    database: Database,
-}
-
-impl AppState {
-    async fn load() -> Self {
-        // let ip = env::var("ip").unwrap();
-        let database = Database::new().await;
-        database.run_migrations().await.unwrap();
-        return Self {
-            client_id: env::var("ClientID").unwrap(),
-            client_secret: env::var("ClientSecret").unwrap(),
-            redirect_uri: format!("http://whatanime.ddns.net:8000/callback"),
-            // ip,
-            anisong_db: AnisongClient::new(),
-            database,
-        };
-    }
-}
+use backend::routes::{
+    analyze_playlist, anime_by_ann_id, anime_by_mal_id, answer_quiz, api_tokens, api_v1, artist,
+    artist_links, callback, confirm_anime, create_api_token, history, import_mal_list,
+    import_status, library, link_anilist, login, login_token, logout, playlist, quiz_results,
+    report, revoke_api_token, search_anime, start_import, start_quiz, stats, track, update,
+};
 
 #[tokio::main]
 async fn main() {
@@ -61,6 +31,20 @@ async fn main() {
         .target(Target::Stdout)
         .init();
 
+    // Maintenance commands, run instead of the server
+    if env::args().nth(1).as_deref() == Some("backfill-names") {
+        let database = Database::new().await;
+        database.run_migrations().await.unwrap();
+        database.backfill_name_variants().await.unwrap();
+        return;
+    }
+    if env::args().nth(1).as_deref() == Some("backfill-franchises") {
+        let database = Database::new().await;
+        database.run_migrations().await.unwrap();
+        database.backfill_franchises().await.unwrap();
+        return;
+    }
+
     task::spawn(async {
         let interval_duration = Duration::from_secs(60 * 60); // 1 hour
         let mut interval = interval(interval_duration);
@@ -78,6 +62,22 @@ async fn main() {
     //.with_expiry(Expiry::OnInactivity(Duration::seconds(10)));
 
     let shared_state = Arc::new(AppState::load().await);
+    if let Err(e) = shared_state.interrupt_running_imports().await {
+        warn!("Could not mark interrupted imports: {:?}", e);
+    }
+
+    let enrich_state = shared_state.clone();
+    task::spawn(async move {
+        let mut interval = interval(Duration::from_secs(60 * 60)); // 1 hour
+        loop {
+            interval.tick().await;
+            match enrich_state.enrich_spotify_artists().await {
+                Ok(0) => {}
+                Ok(updated) => info!("Updated {} spotify artists", updated),
+                Err(e) => warn!("Could not update spotify artists: {:?}", e),
+            }
+        }
+    });
 
     // migrate_database(&shared_state.database).await;
 
@@ -91,15 +91,38 @@ async fn main() {
     let app = Router::new()
         .route("/api/update", get(update))
         .route("/api/login", get(login))
+        .route("/api/login/token", post(login_token))
+        .route("/api/logout", post(logout))
         .route("/callback", get(callback))
         .route("/api/confirm_anime", post(confirm_anime))
         .route("/api/report", post(report))
+        .route("/api/anime/{ann_id}", get(anime_by_ann_id))
+        .route("/api/anime/by-mal/{mal_id}", get(anime_by_mal_id))
+        .route("/api/artist/{ann_id}", get(artist))
+        .route("/api/artist_links", get(artist_links))
+        .route("/api/track/{spotify_id}", get(track))
+        .route("/api/search/anime", get(search_anime))
+        .route("/api/playlist", post(playlist))
+        .route("/api/analyze/playlist", post(analyze_playlist))
+        .route("/api/me/history", get(history))
+        .route("/api/me/stats", get(stats))
+        .route("/api/me/import", post(start_import))
+        .route("/api/me/import/{job_id}", get(import_status))
+        .route("/api/me/library", get(library))
+        .route("/api/me/lists/anilist", post(link_anilist))
+        .route("/api/me/lists/mal", post(import_mal_list))
+        .route("/api/me/tokens", get(api_tokens).post(create_api_token))
+        .route("/api/me/tokens/{token_id}", delete(revoke_api_token))
+        .route("/api/quiz", post(start_quiz))
+        .route("/api/quiz/{quiz_id}", get(quiz_results))
+        .route("/api/quiz/{quiz_id}/answer", post(answer_quiz))
+        .nest("/api/v1", api_v1())
         .layer(session_layer)
         .layer(
             CorsLayer::new()
                 .allow_origin(allowed_origins)
                 .allow_credentials(true)
-                .allow_methods([Method::GET, Method::POST])
+                .allow_methods([Method::GET, Method::POST, Method::DELETE])
                 .allow_headers([AUTHORIZATION, ACCEPT]),
         )
         .with_state(shared_state.clone()); // Enable CORS
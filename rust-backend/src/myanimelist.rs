@@ -0,0 +1,84 @@
+use crate::types::WatchStatus;
+use crate::{Error, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref ANIME_REGEX: Regex = Regex::new(r"(?s)<anime>(.*?)</anime>").unwrap();
+    static ref ID_REGEX: Regex =
+        Regex::new(r"<series_animedb_id>\s*(\d+)\s*</series_animedb_id>").unwrap();
+    static ref STATUS_REGEX: Regex =
+        Regex::new(r"<my_status>\s*(?:<!\[CDATA\[)?\s*([^<\]]+?)\s*(?:\]\]>)?\s*</my_status>")
+            .unwrap();
+}
+
+fn parse_status(status: &str) -> Option<WatchStatus> {
+    match status {
+        "Watching" => Some(WatchStatus::Watching),
+        "Completed" => Some(WatchStatus::Completed),
+        "On-Hold" => Some(WatchStatus::Paused),
+        "Dropped" => Some(WatchStatus::Dropped),
+        "Plan to Watch" => Some(WatchStatus::Planning),
+        _ => None,
+    }
+}
+
+/// Reads the (unzipped) xml list export from MyAnimeList into mal ids and their watch status
+pub fn parse_export(xml: &str) -> Result<Vec<(i32, WatchStatus)>> {
+    if !xml.contains("<myanimelist>") {
+        return Err(Error::InvalidParameter(
+            "Not a MyAnimeList export".to_string(),
+        ));
+    }
+
+    Ok(ANIME_REGEX
+        .captures_iter(xml)
+        .filter_map(|anime| {
+            let anime = anime.get(1)?.as_str();
+            let mal_id = ID_REGEX.captures(anime)?.get(1)?.as_str().parse().ok()?;
+            let status = parse_status(STATUS_REGEX.captures(anime)?.get(1)?.as_str())?;
+            Some((mal_id, status))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<myanimelist>
+    <myinfo>
+        <user_name>someone</user_name>
+    </myinfo>
+    <anime>
+        <series_animedb_id>5114</series_animedb_id>
+        <series_title><![CDATA[Fullmetal Alchemist: Brotherhood]]></series_title>
+        <my_status>Completed</my_status>
+    </anime>
+    <anime>
+        <series_animedb_id>16498</series_animedb_id>
+        <my_status><![CDATA[Plan to Watch]]></my_status>
+    </anime>
+    <anime>
+        <series_animedb_id>1</series_animedb_id>
+        <my_status>Something else</my_status>
+    </anime>
+</myanimelist>"#;
+
+    #[test]
+    fn parses_export() {
+        assert_eq!(
+            parse_export(EXPORT).unwrap(),
+            vec![
+                (5114, WatchStatus::Completed),
+                (16498, WatchStatus::Planning)
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(parse_export("<html></html>").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State, response::IntoResponse};
+use futures::{StreamExt, stream};
+use log::info;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::lists::caller_watch_list;
+use crate::{
+    AppState, Error, Result,
+    database::Database,
+    spotify::{
+        api::{get_album_tracks, get_playlist_tracks},
+        auth::SpotifyAuth,
+        links::SpotifyLink,
+        responses::TrackObject,
+    },
+    types::{AnalysisSummary, NewSong, PlaylistAnalysis, TrackReport},
+};
+
+const MAX_TRACKS: usize = 500;
+// Kept below the database pool size so a single analysis can't starve everyone else
+const MAX_CONCURRENT_TRACKS: usize = 3;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AnalyzeParams {
+    url: String,
+    // Put shows on the users linked anime lists first
+    watched_first: Option<bool>,
+}
+
+async fn analyze_track(app_state: Arc<AppState>, track: Option<TrackObject>) -> TrackReport {
+    let track = match track {
+        Some(track) => track,
+        None => {
+            return TrackReport::Error {
+                title: None,
+                reason: "Not a spotify track".to_string(),
+            };
+        }
+    };
+
+    match app_state
+        .database
+        .get_anime_2(&track, &app_state.anisong_db, Database::ACCURACY_CUTOFF)
+        .await
+    {
+        Ok(NewSong::Hit(hit)) => TrackReport::Hit {
+            song_info: hit.song_info,
+            certainty: hit.certainty,
+            anime_info: hit.anime_info,
+        },
+        Ok(NewSong::Miss(miss)) => TrackReport::Miss {
+            song_info: miss.song_info,
+        },
+        Err(error) => TrackReport::Error {
+            title: Some(track.name),
+            reason: format!("{:?}", error),
+        },
+    }
+}
+
+/// Matches every track of a spotify playlist or album
+#[utoipa::path(
+    post,
+    path = "/analyze/playlist",
+    request_body = AnalyzeParams,
+    responses((status = 200, body = PlaylistAnalysis), (status = 400), (status = 401))
+)]
+pub async fn analyze_playlist(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Json(params): Json<AnalyzeParams>,
+) -> Result<impl IntoResponse> {
+    let link = SpotifyLink::parse(&params.url).ok_or(Error::InvalidParameter(format!(
+        "Not a spotify playlist or album: {}",
+        &params.url
+    )))?;
+
+    let token = &auth.access_token;
+
+    let tracks = match &link {
+        SpotifyLink::Playlist(id) => get_playlist_tracks(id, token, MAX_TRACKS).await?,
+        SpotifyLink::Album(id) => get_album_tracks(id, token, MAX_TRACKS).await?,
+        SpotifyLink::Track(_) => {
+            return Err(Error::InvalidParameter(format!(
+                "Not a spotify playlist or album: {}",
+                &params.url
+            )));
+        }
+    };
+
+    info!("Analyzing {} tracks from {:?}", tracks.len(), &link);
+
+    let mut reports: Vec<TrackReport> = stream::iter(tracks)
+        .map(|track| analyze_track(app_state.clone(), track))
+        .buffered(MAX_CONCURRENT_TRACKS)
+        .collect()
+        .await;
+
+    if let Ok(user_id) = auth.user_id().await {
+        if let Some(watch_list) = caller_watch_list(&app_state, &user_id).await {
+            let watched_first = params.watched_first.is_some_and(|value| value);
+            for report in reports.iter_mut() {
+                if let TrackReport::Hit { anime_info, .. } = report {
+                    watch_list.annotate(anime_info, watched_first);
+                }
+            }
+        }
+    }
+
+    Ok(Json(PlaylistAnalysis {
+        summary: AnalysisSummary::from_reports(&reports),
+        tracks: reports,
+    }))
+}
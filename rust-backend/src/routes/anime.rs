@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+
+use crate::{AppState, Result, types::AnimeSong};
+
+/// Every song of an anime by its AnimeNewsNetwork id, with the spotify tracks bound to each
+#[utoipa::path(
+    get,
+    path = "/anime/{ann_id}",
+    params(("ann_id" = i32, Path, description = "AnimeNewsNetwork id")),
+    responses((status = 200, body = Vec<AnimeSong>), (status = 404))
+)]
+pub async fn anime_by_ann_id(
+    State(app_state): State<Arc<AppState>>,
+    Path(ann_id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    let db_animes = app_state.database.get_animes_by_ann_id(ann_id).await?;
+    let anisongs = app_state.anisong_db.get_animes_by_ann_id(ann_id).await?;
+
+    Ok(Json(
+        app_state
+            .database
+            .get_anime_songs(db_animes, anisongs)
+            .await?,
+    ))
+}
+
+/// Every song of an anime by its MyAnimeList id, with the spotify tracks bound to each
+#[utoipa::path(
+    get,
+    path = "/anime/by-mal/{mal_id}",
+    params(("mal_id" = i32, Path, description = "MyAnimeList id")),
+    responses((status = 200, body = Vec<AnimeSong>), (status = 404))
+)]
+pub async fn anime_by_mal_id(
+    State(app_state): State<Arc<AppState>>,
+    Path(mal_id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    let db_animes = app_state.database.get_animes_by_mal_id(mal_id).await?;
+    let anisongs = app_state
+        .anisong_db
+        .get_animes_by_mal_ids(vec![mal_id])
+        .await?;
+
+    Ok(Json(
+        app_state
+            .database
+            .get_anime_songs(db_animes, anisongs)
+            .await?,
+    ))
+}
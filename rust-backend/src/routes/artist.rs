@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+use crate::{
+    AppState, Error, Result,
+    types::{ArtistDiscography, ArtistLinkAudit},
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ArtistLinkParams {
+    // Only links that matched this well or worse
+    max_score: Option<f32>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+/// An artist with their groups, members, spotify artists and every song they are credited on
+#[utoipa::path(
+    get,
+    path = "/artist/{ann_id}",
+    params(("ann_id" = i32, Path, description = "AnimeNewsNetwork artist id")),
+    responses((status = 200, body = ArtistDiscography), (status = 404))
+)]
+pub async fn artist(
+    State(app_state): State<Arc<AppState>>,
+    Path(ann_id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    let anisongs = app_state
+        .anisong_db
+        .get_animes_by_artists_ids::<false>(vec![ann_id])
+        .await?;
+
+    Ok(Json(
+        app_state
+            .database
+            .get_artist_discography(ann_id, anisongs)
+            .await?,
+    ))
+}
+
+/// Every link between anisong and spotify artists with the names on both sides, worst matches first
+#[utoipa::path(
+    get,
+    path = "/artist_links",
+    params(ArtistLinkParams),
+    responses((status = 200, body = Vec<ArtistLinkAudit>))
+)]
+pub async fn artist_links(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<ArtistLinkParams>,
+) -> Result<impl IntoResponse> {
+    let page = params.page.unwrap_or(0);
+    if page < 0 {
+        return Err(Error::InvalidParameter(format!(
+            "page can't be negative: {}",
+            page
+        )));
+    }
+
+    Ok(Json(
+        app_state
+            .database
+            .get_artist_link_audit(
+                params.max_score,
+                page,
+                params
+                    .per_page
+                    .unwrap_or(DEFAULT_PAGE_SIZE)
+                    .clamp(1, MAX_PAGE_SIZE),
+            )
+            .await?,
+    ))
+}
@@ -1,32 +1,94 @@
 use std::{
-    collections::HashMap,
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use axum::http::HeaderMap;
 use axum::{
     extract::{Query, State},
-    http::HeaderValue,
     response::Redirect,
 };
-use base64::{Engine, engine};
-use reqwest::Client;
+use log::warn;
 use serde::{Deserialize, Serialize};
 use tower_sessions::Session;
+use url::form_urlencoded;
 
-use crate::{AppState, spotify::responses::SpotifyToken};
+use super::tokens::TOKEN_GRANT_KEY;
+use crate::{
+    AppState, Error,
+    spotify::{
+        api::{get_user, request_authorization_token},
+        responses::SpotifyToken,
+    },
+};
 
-//#[derive(Deserialize)]
-// enum CodeOrState {
-//     error(String),
-//     state(String),
-// }
+const FRONTEND_URL: &str = "http://whatanime.ddns.net:5173/";
 
+/// Spotify either sends a code, or an error like access_denied when the user declined
 #[derive(Deserialize, Serialize)]
-pub struct CallbackParams {
+#[serde(untagged)]
+pub enum CallbackParams {
+    Code {
+        code: String,
+        state: String,
+    },
+    Error {
+        error: String,
+        state: Option<String>,
+    },
+}
+
+/// Stores the spotify login that was made for an API token, which has to be of the user the token belongs to
+async fn save_token_grant(
+    app_state: &Arc<AppState>,
+    token_id: i32,
     code: String,
-    state: String,
+) -> crate::Result<()> {
+    let token_info =
+        request_authorization_token(code, &app_state.redirect_uri, None, app_state).await?;
+    let refresh_token = token_info.refresh_token.ok_or(Error::BadOAuth)?;
+    let user = get_user(token_info.access_token).await?;
+
+    if !app_state
+        .database
+        .set_api_token_grant(token_id, &user.id, &refresh_token)
+        .await?
+    {
+        return Err(Error::BadOAuth);
+    }
+    Ok(())
+}
+
+/// Logs in the session with the tokens of a spotify login, PKCE logins have to refresh without our client secret
+pub(super) async fn save_login(
+    session: &Session,
+    token_info: SpotifyToken,
+    pkce: bool,
+) -> crate::Result<()> {
+    // Whoever was logged in before might have used another spotify account, their id is looked up again
+    session.remove::<String>("user_id").await?;
+    session
+        .insert("access_token", token_info.access_token)
+        .await?;
+    session
+        .insert("refresh_token", token_info.refresh_token)
+        .await?;
+    session
+        .insert("scope", token_info.scope.unwrap_or_default())
+        .await?;
+    session.insert("pkce", pkce).await?;
+    session
+        .insert(
+            "expire_time",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+                + token_info.expires_in,
+        )
+        .await?;
+
+    session.save().await?;
+    Ok(())
 }
 
 pub async fn callback(
@@ -37,66 +99,59 @@ pub async fn callback(
     session.load().await.unwrap();
 
     let session_state = session.get::<String>("state").await.unwrap_or(None);
-    if session_state.as_deref() != Some(&params.state) {
-        println!("Sate missmatch occured, probably");
-        println!("{}, {:?}", params.state, session_state);
-        return Err(axum::http::StatusCode::BAD_REQUEST);
-    }
-
-    let client_creds = format!("{}:{}", app_state.client_id, app_state.client_secret);
-    let client_creds_b64 = engine::general_purpose::STANDARD.encode(client_creds);
-
-    let token_data = HashMap::from([
-        ("code", params.code),
-        ("redirect_uri", app_state.redirect_uri.clone()),
-        ("grant_type", "authorization_code".to_string()),
-    ]);
-
-    let mut token_headers = HeaderMap::new();
-    token_headers.insert(
-        "Authorization",
-        HeaderValue::from_str(&format!("Basic {client_creds_b64}")).unwrap(),
-    );
-    token_headers.insert(
-        "Content-Type",
-        HeaderValue::from_static("application/x-www-form-urlencoded"),
-    );
-
-    let token_response = Client::new()
-        .post("https://accounts.spotify.com/api/token")
-        .headers(token_headers)
-        .form(&token_data)
-        .send()
-        .await
-        .unwrap();
 
-    if token_response.status().is_success() {
-        let token_info: SpotifyToken = token_response.json().await.unwrap();
+    let (code, state) = match params {
+        CallbackParams::Code { code, state } => (code, state),
+        CallbackParams::Error { error, .. } => {
+            warn!("Spotify login failed: {}", error);
+            let query = form_urlencoded::Serializer::new(String::new())
+                .append_pair("login_error", &error)
+                .finish();
+            return Ok(Redirect::to(&format!("{}?{}", FRONTEND_URL, query)));
+        }
+    };
 
+    if let Some((_, token_id)) = session
+        .get::<(String, i32)>(TOKEN_GRANT_KEY)
+        .await
+        .unwrap_or(None)
+        .filter(|(grant_state, _)| grant_state == &state)
+    {
         session
-            .insert("access_token", token_info.access_token)
-            .await
-            .unwrap();
-        session
-            .insert("refresh_token", token_info.refresh_token)
-            .await
-            .unwrap();
-        session
-            .insert(
-                "expire_time",
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs()
-                    + token_info.expires_in,
-            )
+            .remove::<(String, i32)>(TOKEN_GRANT_KEY)
             .await
             .unwrap();
+        let (key, value) = match save_token_grant(&app_state, token_id, code).await {
+            Ok(()) => ("token_authorized", token_id.to_string()),
+            Err(error) => {
+                warn!("Authorizing API token {} failed: {:?}", token_id, error);
+                ("login_error", "token_authorization_failed".to_string())
+            }
+        };
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair(key, &value)
+            .finish();
+        return Ok(Redirect::to(&format!("{}?{}", FRONTEND_URL, query)));
+    }
+
+    if session_state.as_deref() != Some(&state) {
+        warn!(
+            "Login state mismatch, got {} but the session has {:?}",
+            state, session_state
+        );
+        return Err(axum::http::StatusCode::BAD_REQUEST);
+    }
 
-        session.save().await.unwrap();
+    let token_info =
+        match request_authorization_token(code, &app_state.redirect_uri, None, &app_state).await {
+            Ok(token_info) => token_info,
+            Err(_) => return Err(axum::http::StatusCode::BAD_REQUEST),
+        };
 
-        return Ok(Redirect::to("http://whatanime.ddns.net:5173/"));
+    if let Err(error) = save_login(&session, token_info, false).await {
+        warn!("Saving the spotify login failed: {:?}", error);
+        return Err(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
     }
 
-    return Err(axum::http::StatusCode::BAD_REQUEST);
+    Ok(Redirect::to(FRONTEND_URL))
 }
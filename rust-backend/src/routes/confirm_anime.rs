@@ -3,22 +3,53 @@ use std::sync::Arc;
 use crate::{Result, spotify::api::get_user};
 use axum::{Json, extract::State, response::IntoResponse};
 use serde::{Deserialize, Serialize};
-use tower_sessions::Session;
+use utoipa::ToSchema;
 
-use crate::{AppState, spotify::api::get_song};
+use crate::{
+    AppState,
+    spotify::{api::get_song, auth::SpotifyAuth},
+};
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, ToSchema)]
 pub struct ConfirmationParams {
     pub song_name: String,
     pub artist_ids: Vec<i32>,
     pub spotify_id: String,
 }
 
+/// Names of the animes that were and were not linked to the track
+#[derive(Serialize, ToSchema)]
+pub struct ConfirmationResult {
+    pub added: Vec<String>,
+    pub failed: Vec<String>,
+}
+
 pub async fn confirm_anime(
     State(app_state): State<Arc<AppState>>,
-    session: Session,
+    auth: SpotifyAuth,
     Json(params): Json<ConfirmationParams>,
 ) -> Result<impl IntoResponse> {
+    let result = add_confirmed_anime(&app_state, &auth, params).await?;
+
+    let mut string_response = String::new();
+    if result.added.len() > 0 {
+        string_response.push_str(&format!(
+            "Succeded in adding: {}\n",
+            result.added.join(", ")
+        ));
+    }
+    if result.failed.len() > 0 {
+        string_response.push_str(&format!("Failed in adding: {}", result.failed.join(", ")));
+    }
+
+    Ok(Json(string_response))
+}
+
+pub(super) async fn add_confirmed_anime(
+    app_state: &Arc<AppState>,
+    auth: &SpotifyAuth,
+    params: ConfirmationParams,
+) -> Result<ConfirmationResult> {
     let anisongs = app_state
         .anisong_db
         .get_exact_song(params.artist_ids, params.song_name)
@@ -27,24 +58,11 @@ pub async fn confirm_anime(
 
     let track = get_song(
         params.spotify_id,
-        session
-            .get::<String>("access_token")
-            .await
-            .unwrap()
-            .unwrap(),
+        app_state.client_token.access_token().await?,
     )
-    .await
-    .unwrap();
+    .await?;
 
-    let user = get_user(
-        session
-            .get::<String>("access_token")
-            .await
-            .unwrap()
-            .unwrap(),
-    )
-    .await
-    .unwrap();
+    let user = get_user(auth.access_token.clone()).await?;
 
     let mut successes = Vec::new();
     let mut fails = Vec::new();
@@ -69,16 +87,11 @@ pub async fn confirm_anime(
         app_state
             .database
             .try_add_artists(&anisongs[0].artists, &track.artists)
-            .await;
+            .await?;
     }
 
-    let mut string_response = String::new();
-    if successes.len() > 0 {
-        string_response.push_str(&format!("Succeded in adding: {}\n", successes.join(", ")));
-    }
-    if fails.len() > 0 {
-        string_response.push_str(&format!("Failed in adding: {}", fails.join(", ")));
-    }
-
-    Ok(Json(string_response))
+    Ok(ConfirmationResult {
+        added: successes,
+        failed: fails,
+    })
 }
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::HeaderMap,
+    response::IntoResponse,
+};
+use axum_sessions::async_session::chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+use utoipa::IntoParams;
+
+use super::lists::caller_watch_list;
+use crate::{
+    AppState, Error, Result,
+    auth::caller_user_id,
+    database::history::HistoryFilter,
+    types::{ApiScope, HistoryPage, TrackType},
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryParams {
+    page: Option<i64>,
+    per_page: Option<i64>,
+    ann_id: Option<i32>,
+    track_type: Option<TrackType>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    // Put shows on the users linked anime lists first
+    watched_first: Option<bool>,
+}
+
+/// The tracks the user was identified listening to, most recent first
+#[utoipa::path(
+    get,
+    path = "/me/history",
+    params(HistoryParams),
+    responses((status = 200, body = HistoryPage), (status = 401))
+)]
+pub async fn history(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    session: Session,
+    Query(params): Query<HistoryParams>,
+) -> Result<impl IntoResponse> {
+    let page = params.page.unwrap_or(0);
+    if page < 0 {
+        return Err(Error::InvalidParameter(format!(
+            "page can't be negative: {}",
+            page
+        )));
+    }
+    let user_id = caller_user_id(&headers, &session, &app_state, ApiScope::History).await?;
+
+    let filter = HistoryFilter {
+        ann_id: params.ann_id,
+        track_type: params.track_type,
+        from: params.from,
+        to: params.to,
+    };
+
+    let mut history = app_state
+        .database
+        .get_listen_history(
+            &user_id,
+            &filter,
+            page,
+            params
+                .per_page
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .clamp(1, MAX_PAGE_SIZE),
+        )
+        .await?;
+
+    if let Some(watch_list) = caller_watch_list(&app_state, &user_id).await {
+        let watched_first = params.watched_first.is_some_and(|value| value);
+        for entry in history.entries.iter_mut() {
+            watch_list.annotate(&mut entry.anime_info, watched_first);
+        }
+    }
+
+    Ok(Json(history))
+}
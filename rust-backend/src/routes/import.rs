@@ -0,0 +1,225 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::login::{RECENTLY_PLAYED_SCOPE, SAVED_TRACKS_SCOPE};
+use crate::{
+    AppState, Error, Result,
+    database::Database,
+    spotify::{
+        api::{get_recently_played, get_saved_tracks},
+        auth::SpotifyAuth,
+        responses::TrackObject,
+    },
+    types::{ImportJob, ImportSource, LibraryTrack, NewSong},
+};
+
+const MAX_SAVED_TRACKS: usize = 2000;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImportParams {
+    source: ImportSource,
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LibraryParams {
+    source: Option<ImportSource>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportStarted {
+    job_id: i32,
+    total: usize,
+}
+
+/// Identifies the tracks one at a time so an import doesn't hog the database pool
+async fn run_import(
+    app_state: Arc<AppState>,
+    job_id: i32,
+    user_id: String,
+    source: ImportSource,
+    tracks: Vec<TrackObject>,
+) {
+    for track in tracks {
+        let saved = match app_state
+            .database
+            .get_anime_2(&track, &app_state.anisong_db, Database::ACCURACY_CUTOFF)
+            .await
+        {
+            Ok(NewSong::Hit(hit)) => {
+                let ann_song_ids = hit.anime_info.iter().map(|a| a.ann_song_id).collect();
+                app_state
+                    .database
+                    .save_import_result(
+                        job_id,
+                        &user_id,
+                        source,
+                        &track.id,
+                        &ann_song_ids,
+                        Some(hit.certainty),
+                    )
+                    .await
+            }
+            Ok(NewSong::Miss(_)) => {
+                app_state
+                    .database
+                    .save_import_result(job_id, &user_id, source, &track.id, &vec![], None)
+                    .await
+            }
+            Err(error) => {
+                warn!(
+                    "Failed to identify {} during import: {:?}",
+                    &track.id, error
+                );
+                app_state
+                    .database
+                    .count_import_progress(job_id, false)
+                    .await
+            }
+        };
+        if let Err(error) = saved {
+            warn!("Failed to save import of {}: {:?}", &track.id, error);
+        }
+    }
+
+    info!("Finished import job {}", job_id);
+}
+
+/// Runs the import in its own task so that a panic still marks the job as finished
+async fn supervise_import(
+    app_state: Arc<AppState>,
+    job_id: i32,
+    user_id: String,
+    source: ImportSource,
+    tracks: Vec<TrackObject>,
+) {
+    let error = match tokio::spawn(run_import(
+        app_state.clone(),
+        job_id,
+        user_id,
+        source,
+        tracks,
+    ))
+    .await
+    {
+        Ok(()) => None,
+        Err(error) => {
+            warn!("Import job {} stopped: {:?}", job_id, error);
+            Some("Import stopped unexpectedly")
+        }
+    };
+
+    if let Err(error) = app_state.database.finish_import_job(job_id, error).await {
+        warn!("Failed to finish import job {}: {:?}", job_id, error);
+    }
+}
+
+/// Starts matching the recently played or saved tracks of the user in the background
+#[utoipa::path(
+    post,
+    path = "/me/import",
+    request_body = ImportParams,
+    responses((status = 200, body = ImportStarted), (status = 401), (status = 403), (status = 409))
+)]
+pub async fn start_import(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Json(params): Json<ImportParams>,
+) -> Result<impl IntoResponse> {
+    let token = &auth.access_token;
+    let user_id = auth.user_id().await?;
+
+    // Checked again when the job is made, this just saves fetching the tracks for nothing
+    if app_state.database.has_running_import(&user_id).await? {
+        return Err(Error::Conflict("An import is already running".to_string()));
+    }
+
+    // The tracks are fetched up front since the access token might expire during a long import
+    let tracks = match params.source {
+        ImportSource::RecentlyPlayed => {
+            auth.require_scope(RECENTLY_PLAYED_SCOPE).await?;
+            get_recently_played(token).await?
+        }
+        ImportSource::SavedTracks => {
+            auth.require_scope(SAVED_TRACKS_SCOPE).await?;
+            get_saved_tracks(token, MAX_SAVED_TRACKS).await?
+        }
+    };
+    let tracks: Vec<TrackObject> = tracks.into_iter().flatten().collect();
+
+    let job_id = app_state
+        .database
+        .create_import_job(&user_id, params.source, tracks.len() as i32)
+        .await?;
+
+    info!(
+        "Starting import job {} of {} tracks from {:?}",
+        job_id,
+        tracks.len(),
+        params.source
+    );
+
+    let total = tracks.len();
+    tokio::spawn(supervise_import(
+        app_state.clone(),
+        job_id,
+        user_id,
+        params.source,
+        tracks,
+    ));
+
+    Ok(Json(ImportStarted { job_id, total }))
+}
+
+/// How far along an import is
+#[utoipa::path(
+    get,
+    path = "/me/import/{job_id}",
+    params(("job_id" = i32, Path, description = "Id returned when the import was started")),
+    responses((status = 200, body = ImportJob), (status = 404))
+)]
+pub async fn import_status(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Path(job_id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    let user_id = auth.user_id().await?;
+
+    Ok(Json(
+        app_state
+            .database
+            .get_import_job(&user_id, job_id)
+            .await?
+            .ok_or(Error::NotFound)?,
+    ))
+}
+
+/// The imported tracks that are from an anime
+#[utoipa::path(
+    get,
+    path = "/me/library",
+    params(LibraryParams),
+    responses((status = 200, body = Vec<LibraryTrack>), (status = 401))
+)]
+pub async fn library(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Query(params): Query<LibraryParams>,
+) -> Result<impl IntoResponse> {
+    let user_id = auth.user_id().await?;
+
+    Ok(Json(
+        app_state
+            .database
+            .get_library_anime_tracks(&user_id, params.source)
+            .await?,
+    ))
+}
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State, response::IntoResponse};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    AppState, Result,
+    anilist::lists::fetch_user_list,
+    myanimelist::parse_export,
+    spotify::auth::SpotifyAuth,
+    types::{ListSource, WatchList},
+};
+
+/// The linked anime lists of a user, None when they have none so nothing needs annotating
+pub(super) async fn caller_watch_list(
+    app_state: &Arc<AppState>,
+    user_id: &String,
+) -> Option<WatchList> {
+    match app_state.database.get_watch_list(user_id).await {
+        Ok(watch_list) if !watch_list.is_empty() => Some(watch_list),
+        Ok(_) => None,
+        Err(error) => {
+            warn!("Failed to get watch list: {:?}", error);
+            None
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AnilistParams {
+    username: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LinkedList {
+    entries: usize,
+}
+
+/// Replaces the users AniList watch statuses with those of a public AniList profile
+#[utoipa::path(
+    post,
+    path = "/me/lists/anilist",
+    request_body = AnilistParams,
+    responses((status = 200, body = LinkedList), (status = 401), (status = 404))
+)]
+pub async fn link_anilist(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Json(params): Json<AnilistParams>,
+) -> Result<impl IntoResponse> {
+    let user_id = auth.user_id().await?;
+
+    let entries = fetch_user_list(&params.username)
+        .await?
+        .into_iter()
+        .map(|e| (Some(e.anilist_id), e.mal_id, e.status))
+        .collect();
+
+    app_state
+        .database
+        .replace_watch_list(&user_id, ListSource::Anilist, &entries)
+        .await?;
+
+    info!(
+        "Linked AniList {} with {} entries",
+        &params.username,
+        entries.len()
+    );
+
+    Ok(Json(LinkedList {
+        entries: entries.len(),
+    }))
+}
+
+/// Takes the unzipped xml export from MyAnimeList as the request body
+#[utoipa::path(
+    post,
+    path = "/me/lists/mal",
+    request_body(content = String, content_type = "application/xml"),
+    responses((status = 200, body = LinkedList), (status = 400), (status = 401))
+)]
+pub async fn import_mal_list(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    body: String,
+) -> Result<impl IntoResponse> {
+    let user_id = auth.user_id().await?;
+
+    let entries = parse_export(&body)?
+        .into_iter()
+        .map(|(mal_id, status)| (None, Some(mal_id), status))
+        .collect();
+
+    app_state
+        .database
+        .replace_watch_list(&user_id, ListSource::MyAnimeList, &entries)
+        .await?;
+
+    Ok(Json(LinkedList {
+        entries: entries.len(),
+    }))
+}
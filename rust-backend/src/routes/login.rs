@@ -1,33 +1,206 @@
-use crate::AppState;
+use super::callback::save_login;
+use crate::{AppState, Error, Result, spotify::api::request_authorization_token};
 
-use axum::{extract::State, response::IntoResponse};
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::IntoResponse,
+};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, sync::Arc};
 use tower_sessions::Session;
 use url::form_urlencoded;
 
-pub async fn login(State(app_state): State<Arc<AppState>>, session: Session) -> impl IntoResponse {
+pub const PLAYLIST_SCOPE: &str = "playlist-modify-private";
+pub const RECENTLY_PLAYED_SCOPE: &str = "user-read-recently-played";
+pub const SAVED_TRACKS_SCOPE: &str = "user-library-read";
+// All an API token needs from spotify to identify what its user is playing
+pub const TOKEN_GRANT_SCOPE: &str = "user-read-playback-state user-read-currently-playing";
+// Where spotify may send the code of a PKCE login, these also have to be registered with the spotify app
+const PUBLIC_CLIENT_REDIRECT_URIS: &[&str] =
+    &["http://127.0.0.1:8765/callback", "whatanime://callback"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginParams {
+    // Opt in to letting us make playlists
+    playlists: Option<bool>,
+    // Opt in to importing recently played and saved tracks
+    library: Option<bool>,
+    // A PKCE login for public clients like the desktop and mobile companions, which keep the
+    // code_verifier to themselves and get the code at their own redirect_uri
+    code_challenge: Option<String>,
+    redirect_uri: Option<String>,
+    // Public clients check the state themselves, as spotify redirects to them instead of us
+    state: Option<String>,
+}
+
+/// What a public client sends to trade the code of its PKCE login
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginTokenParams {
+    code: String,
+    code_verifier: String,
+    redirect_uri: String,
+}
+
+/// The spotify tokens of a PKCE login, the client can use them with spotify directly as well
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginTokens {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: u64,
+    scope: String,
+}
+
+/// A S256 code_challenge is the unpadded base64url encoding of a sha256 hash
+fn is_code_challenge(code_challenge: &str) -> bool {
+    code_challenge.len() == 43
+        && code_challenge
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+fn check_redirect_uri(redirect_uri: &str) -> Result<()> {
+    if PUBLIC_CLIENT_REDIRECT_URIS.contains(&redirect_uri) {
+        Ok(())
+    } else {
+        Err(Error::InvalidParameter(format!(
+            "Redirect uri not allowed: {}",
+            redirect_uri
+        )))
+    }
+}
+
+/// A random value for the state parameter, checked against the session when spotify calls back
+pub(super) fn new_state() -> String {
     let random_bytes: [u8; 16] = rand::rng().random();
-    let scope =
-        "user-read-private user-read-email user-read-playback-state user-read-currently-playing";
-    let state = hex::encode(random_bytes);
+    hex::encode(random_bytes)
+}
 
-    session.insert("state", state.clone()).await.unwrap();
+/// The spotify page where the user approves access, spotify then calls back with the code and state
+pub(super) fn authorize_url(app_state: &AppState, state: String, scope: String) -> String {
+    authorize_url_with(
+        app_state,
+        state,
+        scope,
+        app_state.redirect_uri.clone(),
+        HashMap::new(),
+    )
+}
 
-    let auth_params = HashMap::from([
+fn authorize_url_with(
+    app_state: &AppState,
+    state: String,
+    scope: String,
+    redirect_uri: String,
+    mut auth_params: HashMap<&'static str, String>,
+) -> String {
+    auth_params.extend([
         ("client_id", app_state.client_id.clone()),
         ("response_type", "code".to_string()),
-        ("redirect_uri", app_state.redirect_uri.clone()),
+        ("redirect_uri", redirect_uri),
         ("state", state),
-        ("scope", scope.to_string()),
+        ("scope", scope),
     ]);
 
-    let auth_url = format!(
+    format!(
         "https://accounts.spotify.com/authorize?{}",
         form_urlencoded::Serializer::new(String::new())
             .extend_pairs(auth_params)
             .finish()
-    );
+    )
+}
+
+pub async fn login(
+    State(app_state): State<Arc<AppState>>,
+    session: Session,
+    Query(params): Query<LoginParams>,
+) -> Result<impl IntoResponse> {
+    let mut scope =
+        "user-read-private user-read-email user-read-playback-state user-read-currently-playing"
+            .to_string();
+    if params.playlists.is_some_and(|value| value) {
+        scope.push_str(&format!(" {}", PLAYLIST_SCOPE));
+    }
+    if params.library.is_some_and(|value| value) {
+        scope.push_str(&format!(
+            " {} {}",
+            RECENTLY_PLAYED_SCOPE, SAVED_TRACKS_SCOPE
+        ));
+    }
+
+    let auth_url = match params.code_challenge {
+        Some(code_challenge) => {
+            if !is_code_challenge(&code_challenge) {
+                return Err(Error::InvalidParameter(
+                    "code_challenge has to be a S256 challenge".to_string(),
+                ));
+            }
+            let redirect_uri = params.redirect_uri.ok_or(Error::InvalidParameter(
+                "A PKCE login needs a redirect_uri".to_string(),
+            ))?;
+            check_redirect_uri(&redirect_uri)?;
+
+            let auth_params = HashMap::from([
+                ("code_challenge_method", "S256".to_string()),
+                ("code_challenge", code_challenge),
+            ]);
+            let state = params.state.unwrap_or_else(new_state);
+            authorize_url_with(&app_state, state, scope, redirect_uri, auth_params)
+        }
+        None => {
+            let state = new_state();
+            session.insert("state", state.clone()).await?;
+            authorize_url(&app_state, state, scope)
+        }
+    };
+
+    Ok(axum::response::Redirect::to(&auth_url))
+}
+
+/// Trades the code of a PKCE login for tokens, which also logs in the session of the client
+pub async fn login_token(
+    State(app_state): State<Arc<AppState>>,
+    session: Session,
+    Json(params): Json<LoginTokenParams>,
+) -> Result<impl IntoResponse> {
+    check_redirect_uri(&params.redirect_uri)?;
+
+    let token_info = request_authorization_token(
+        params.code,
+        &params.redirect_uri,
+        Some(params.code_verifier),
+        &app_state,
+    )
+    .await?;
+
+    let tokens = LoginTokens {
+        access_token: token_info.access_token.clone(),
+        refresh_token: token_info.refresh_token.clone(),
+        expires_in: token_info.expires_in,
+        scope: token_info.scope.clone().unwrap_or_default(),
+    };
+    save_login(&session, token_info, true).await?;
+
+    Ok(Json(tokens))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_the_rfc_7636_challenge() {
+        assert!(is_code_challenge(
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        ));
+    }
 
-    return axum::response::Redirect::to(&auth_url);
+    #[test]
+    fn rejects_plain_challenges() {
+        assert!(!is_code_challenge("not a challenge"));
+        assert!(!is_code_challenge(
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM="
+        ));
+    }
 }
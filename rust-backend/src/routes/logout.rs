@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+use utoipa::IntoParams;
+
+use crate::{AppState, Result, spotify::api::session_user_id};
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LogoutParams {
+    // Also revoke every API token, they keep their own spotify login
+    everywhere: Option<bool>,
+}
+
+/// Revokes the API tokens of the user logged in to the session, if anyone is. The session can't be
+/// refreshed while logging out, so this uses the user id it knows or its access token as it is
+async fn revoke_api_tokens(app_state: &AppState, session: &Session) -> Result<()> {
+    let Some(access_token) = session.get::<String>("access_token").await? else {
+        return Ok(());
+    };
+    let user_id = session_user_id(session, &access_token).await?;
+    let revoked = app_state.database.revoke_all_api_tokens(&user_id).await?;
+
+    let mut cache = app_state.api_token_cache.lock().unwrap();
+    for token_id in revoked {
+        cache.remove(&token_id);
+    }
+    Ok(())
+}
+
+/// Forgets the spotify login of the session, spotify has no way for us to revoke the tokens themselves.
+/// The session is cleared even when revoking the API tokens fails
+#[utoipa::path(post, path = "/logout", params(LogoutParams), responses((status = 204)))]
+pub async fn logout(
+    State(app_state): State<Arc<AppState>>,
+    session: Session,
+    Query(params): Query<LogoutParams>,
+) -> Result<impl IntoResponse> {
+    let revoked = if params.everywhere.is_some_and(|value| value) {
+        revoke_api_tokens(&app_state, &session).await
+    } else {
+        Ok(())
+    };
+
+    session.flush().await?;
+    revoked?;
+    Ok(StatusCode::NO_CONTENT)
+}
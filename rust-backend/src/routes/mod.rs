@@ -1,11 +1,39 @@
+mod analyze;
+mod anime;
+mod artist;
 mod callback;
 mod confirm_anime;
+mod history;
+mod import;
+mod lists;
 mod login;
+mod logout;
+mod playlist;
+mod quiz;
 mod report;
+mod search;
+mod stats;
+mod tokens;
+mod track;
 mod update;
+mod v1;
 
+pub use analyze::analyze_playlist;
+pub use anime::{anime_by_ann_id, anime_by_mal_id};
+pub use artist::{artist, artist_links};
 pub use callback::callback;
 pub use confirm_anime::confirm_anime;
-pub use login::login;
+pub use history::history;
+pub use import::{import_status, library, start_import};
+pub use lists::{import_mal_list, link_anilist};
+pub use login::{login, login_token};
+pub use logout::logout;
+pub use playlist::playlist;
+pub use quiz::{answer_quiz, quiz_results, start_quiz};
 pub use report::report;
+pub use search::search_anime;
+pub use stats::stats;
+pub use tokens::{api_tokens, create_api_token, revoke_api_token};
+pub use track::track;
 pub use update::update;
+pub use v1::api_v1;
@@ -0,0 +1,184 @@
+use std::sync::Arc;
+
+use axum::{Json, extract::State, response::IntoResponse};
+use axum_sessions::async_session::chrono::{DateTime, Utc};
+use itertools::Itertools;
+use log::info;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::login::PLAYLIST_SCOPE;
+use crate::{
+    AppState, Error, Result,
+    spotify::{
+        api::{create_playlist, get_user, replace_playlist_tracks},
+        auth::SpotifyAuth,
+    },
+    types::TrackType,
+};
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlaylistSource {
+    SongGroups {
+        group_ids: Vec<i32>,
+    },
+    Anime {
+        ann_id: i32,
+        track_type: Option<TrackType>,
+    },
+    Artist {
+        ann_id: i32,
+    },
+    // The songs the user has been identified listening to, most recently heard first
+    History {
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        track_type: Option<TrackType>,
+    },
+}
+
+impl PlaylistSource {
+    /// Identifies the playlist so that generating it again updates the old one
+    fn key(&self) -> String {
+        match self {
+            Self::SongGroups { group_ids } => {
+                format!("groups:{}", group_ids.iter().sorted().dedup().join(","))
+            }
+            Self::Anime { ann_id, track_type } => format!(
+                "anime:{}:{}",
+                ann_id,
+                track_type.map_or("all".to_string(), |t| t.discriminant().to_string())
+            ),
+            Self::Artist { ann_id } => format!("artist:{}", ann_id),
+            Self::History {
+                since,
+                until,
+                track_type,
+            } => format!(
+                "history:{}:{}:{}",
+                since.map_or("any".to_string(), |t| t.to_rfc3339()),
+                until.map_or("any".to_string(), |t| t.to_rfc3339()),
+                track_type.map_or("all".to_string(), |t| t.discriminant().to_string())
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PlaylistParams {
+    name: String,
+    source: PlaylistSource,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PlaylistResult {
+    playlist_id: String,
+    url: String,
+    track_count: usize,
+    // Song groups without any bound spotify track
+    missing_tracks: usize,
+}
+
+/// Makes a spotify playlist of the songs from the source, or updates the one made from it before
+#[utoipa::path(
+    post,
+    path = "/playlist",
+    request_body = PlaylistParams,
+    responses((status = 200, body = PlaylistResult), (status = 401), (status = 403), (status = 404))
+)]
+pub async fn playlist(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Json(params): Json<PlaylistParams>,
+) -> Result<impl IntoResponse> {
+    auth.require_scope(PLAYLIST_SCOPE).await?;
+
+    let token = &auth.access_token;
+
+    let group_ids = match &params.source {
+        PlaylistSource::SongGroups { group_ids } => group_ids.clone(),
+        PlaylistSource::Anime { ann_id, track_type } => {
+            app_state
+                .database
+                .get_group_ids_by_ann_id(*ann_id, *track_type)
+                .await?
+        }
+        PlaylistSource::Artist { ann_id } => {
+            let artist = app_state
+                .database
+                .get_artists_by_ann_ids(&vec![*ann_id])
+                .await?
+                .into_iter()
+                .next()
+                .ok_or(Error::NotFound)?;
+            let mut animes = app_state.database.get_animes_by_performer(&artist).await?;
+            animes.sort_by(|a, b| a.song_name.cmp(&b.song_name));
+            animes.iter().filter_map(|a| a.song_group_id).collect()
+        }
+        PlaylistSource::History {
+            since,
+            until,
+            track_type,
+        } => {
+            app_state
+                .database
+                .get_group_ids_by_history(&auth.user_id().await?, *since, *until, *track_type)
+                .await?
+        }
+    };
+
+    let spotify_ids = app_state
+        .database
+        .get_playlist_spotify_ids(&group_ids)
+        .await?;
+    let missing_tracks = group_ids.iter().unique().count() - spotify_ids.len();
+
+    let user = get_user(token.clone()).await?;
+    let source_key = params.source.key();
+
+    let existing = app_state
+        .database
+        .get_generated_playlist(&user.id, &source_key)
+        .await?;
+
+    // The user might have deleted the playlist we made last time
+    let updated = match &existing {
+        Some(playlist_id) => replace_playlist_tracks(token, playlist_id, &spotify_ids).await?,
+        None => None,
+    };
+
+    let playlist_id = match (existing, updated) {
+        (Some(playlist_id), Some(())) => playlist_id,
+        _ => {
+            let playlist = create_playlist(
+                token,
+                &user.id,
+                &params.name,
+                &"Made by WhatAnime".to_string(),
+            )
+            .await?;
+            replace_playlist_tracks(token, &playlist.id, &spotify_ids).await?;
+            playlist.id
+        }
+    };
+
+    app_state
+        .database
+        .save_generated_playlist(&user.id, &source_key, &playlist_id, &params.name)
+        .await?;
+
+    info!(
+        "Generated playlist {} with {} tracks for {:?}",
+        &source_key,
+        spotify_ids.len(),
+        user.display_name
+    );
+
+    Ok(Json(PlaylistResult {
+        url: format!("https://open.spotify.com/playlist/{}", &playlist_id),
+        playlist_id,
+        track_count: spotify_ids.len(),
+        missing_tracks,
+    }))
+}
@@ -0,0 +1,241 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    AppState, Error, Result,
+    database::quiz::QuizFilter,
+    japanese_processing::process_similarity,
+    spotify::auth::SpotifyAuth,
+    types::{
+        FrontendAnimeEntry, Quiz, QuizAnswerResult, QuizQuestion, QuizQuestionResult, QuizResults,
+        TrackType,
+    },
+};
+
+const DEFAULT_QUESTIONS: usize = 10;
+const MAX_QUESTIONS: usize = 30;
+/// Similarity (0 - 100) an answer needs to any accepted title to count as correct
+const CORRECT_ANSWER_LIMIT: f32 = 80.0;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct QuizParams {
+    count: Option<usize>,
+    // Only songs the user has listened to
+    from_history: Option<bool>,
+    year_min: Option<i32>,
+    year_max: Option<i32>,
+    track_type: Option<TrackType>,
+    difficulty_min: Option<f64>,
+    difficulty_max: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AnswerParams {
+    position: i32,
+    answer: String,
+}
+
+/// Makes a quiz of random anime songs, optionally only ones the user has listened to
+#[utoipa::path(
+    post,
+    path = "/quiz",
+    request_body = QuizParams,
+    responses((status = 200, body = Quiz), (status = 401), (status = 404))
+)]
+pub async fn start_quiz(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Json(params): Json<QuizParams>,
+) -> Result<impl IntoResponse> {
+    let user_id = auth.user_id().await?;
+
+    let count = params
+        .count
+        .unwrap_or(DEFAULT_QUESTIONS)
+        .clamp(1, MAX_QUESTIONS);
+
+    let filter = QuizFilter {
+        history_of: params
+            .from_history
+            .is_some_and(|value| value)
+            .then(|| user_id.clone()),
+        year_min: params.year_min,
+        year_max: params.year_max,
+        track_type: params.track_type,
+        difficulty_min: params.difficulty_min,
+        difficulty_max: params.difficulty_max,
+    };
+
+    let picked = app_state
+        .database
+        .get_quiz_candidates(&filter, count as i64)
+        .await?;
+
+    if picked.is_empty() {
+        return Err(Error::NotFound);
+    }
+
+    let spotify_ids = app_state
+        .database
+        .get_spotify_ids_by_group_ids(picked.iter().filter_map(|a| a.song_group_id).collect())
+        .await?;
+
+    let quiz_id = app_state
+        .database
+        .create_quiz(
+            &user_id,
+            &picked
+                .iter()
+                .map(|anime| {
+                    (
+                        anime.ann_song_id,
+                        anime.titles().into_iter().cloned().collect(),
+                    )
+                })
+                .collect(),
+        )
+        .await?;
+
+    info!("Started quiz {} with {} questions", quiz_id, picked.len());
+
+    Ok(Json(Quiz {
+        quiz_id,
+        questions: picked
+            .iter()
+            .enumerate()
+            .map(|(position, anime)| QuizQuestion {
+                position: position as i32,
+                audio: anime
+                    .audio_url
+                    .clone()
+                    .or(anime.mq_url.clone())
+                    .or(anime.hq_url.clone())
+                    .unwrap_or_default(),
+                spotify_id: anime
+                    .song_group_id
+                    .and_then(|id| spotify_ids.get(&id))
+                    .and_then(|ids| ids.first().cloned()),
+                difficulty: anime.song_difficulty,
+            })
+            .collect(),
+    }))
+}
+
+/// Guesses the anime of a question, each question can be answered once
+#[utoipa::path(
+    post,
+    path = "/quiz/{quiz_id}/answer",
+    params(("quiz_id" = i32, Path, description = "Id of the quiz")),
+    request_body = AnswerParams,
+    responses((status = 200, body = QuizAnswerResult), (status = 404), (status = 409))
+)]
+pub async fn answer_quiz(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Path(quiz_id): Path<i32>,
+    Json(params): Json<AnswerParams>,
+) -> Result<impl IntoResponse> {
+    let user_id = auth.user_id().await?;
+
+    let question = app_state
+        .database
+        .get_quiz_questions(&user_id, quiz_id)
+        .await?
+        .ok_or(Error::NotFound)?
+        .into_iter()
+        .find(|q| q.position == params.position)
+        .ok_or(Error::NotFound)?;
+
+    if question.answer.is_some() {
+        return Err(Error::Conflict("Question already answered".to_string()));
+    }
+
+    let score = question
+        .accepted_titles
+        .iter()
+        .map(|title| process_similarity(title, &params.answer))
+        .fold(0.0, f32::max);
+    let correct = score >= CORRECT_ANSWER_LIMIT;
+
+    app_state
+        .database
+        .save_quiz_answer(quiz_id, params.position, &params.answer, score, correct)
+        .await?;
+
+    let anime_info = app_state
+        .database
+        .get_animes_by_ann_song_ids(&vec![question.ann_song_id])
+        .await?
+        .first()
+        .map(FrontendAnimeEntry::from_db_anime);
+
+    Ok(Json(QuizAnswerResult {
+        correct,
+        score,
+        anime_info,
+    }))
+}
+
+/// The answers given so far, with the anime of every answered question
+#[utoipa::path(
+    get,
+    path = "/quiz/{quiz_id}",
+    params(("quiz_id" = i32, Path, description = "Id of the quiz")),
+    responses((status = 200, body = QuizResults), (status = 404))
+)]
+pub async fn quiz_results(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Path(quiz_id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    let user_id = auth.user_id().await?;
+
+    let questions = app_state
+        .database
+        .get_quiz_questions(&user_id, quiz_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let answered_ids = questions
+        .iter()
+        .filter(|q| q.answer.is_some())
+        .map(|q| q.ann_song_id)
+        .collect();
+    let mut animes: HashMap<i32, FrontendAnimeEntry> = app_state
+        .database
+        .get_animes_by_ann_song_ids(&answered_ids)
+        .await?
+        .iter()
+        .map(|a| (a.ann_song_id, FrontendAnimeEntry::from_db_anime(a)))
+        .collect();
+
+    let results: Vec<QuizQuestionResult> = questions
+        .into_iter()
+        .map(|q| QuizQuestionResult {
+            position: q.position,
+            anime_info: q
+                .answer
+                .as_ref()
+                .and_then(|_| animes.remove(&q.ann_song_id)),
+            answer: q.answer,
+            score: q.score,
+            correct: q.correct,
+        })
+        .collect();
+
+    Ok(Json(QuizResults {
+        quiz_id,
+        total: results.len(),
+        answered: results.iter().filter(|q| q.answer.is_some()).count(),
+        correct: results.iter().filter(|q| q.correct == Some(true)).count(),
+        questions: results,
+    }))
+}
@@ -1,12 +1,15 @@
 use std::sync::Arc;
 
-use crate::{AppState, Result, spotify::api::get_user};
+use crate::{
+    AppState, Result,
+    spotify::{api::get_user, auth::SpotifyAuth},
+};
 use axum::{Json, extract::State, response::IntoResponse};
 use log::info;
 use serde::{Deserialize, Serialize};
-use tower_sessions::Session;
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct ReportParams {
     spotify_id: String,
     ann_song_id: i32,
@@ -15,12 +18,11 @@ pub struct ReportParams {
 
 pub async fn report(
     State(app_state): State<Arc<AppState>>,
-    session: Session,
+    auth: Option<SpotifyAuth>,
     Json(params): Json<ReportParams>,
 ) -> Result<impl IntoResponse> {
-    let token = session.get::<String>("access_token").await.unwrap_or(None);
-    let user = match token {
-        Some(token) => Some(get_user(token).await.unwrap()),
+    let user = match auth {
+        Some(auth) => Some(get_user(auth.access_token).await?),
         None => None,
     };
 
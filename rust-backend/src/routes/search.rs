@@ -0,0 +1,79 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+use crate::{
+    AppState, Error, Result,
+    types::{AnimeSearchResult, SongFilters},
+};
+
+/// Word similarity (0.0 - 1.0) a title needs to the query
+const TITLE_SIMILARITY_LIMIT: f32 = 0.6;
+const DEFAULT_RESULTS: i64 = 20;
+const MAX_RESULTS: i64 = 100;
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchParams {
+    q: String,
+    limit: Option<i64>,
+    hide_dubs: Option<bool>,
+    hide_rebroadcasts: Option<bool>,
+    hide_instrumentals: Option<bool>,
+    hide_chanting: Option<bool>,
+    hide_character: Option<bool>,
+}
+
+impl SearchParams {
+    fn filters(&self) -> SongFilters {
+        SongFilters::from_flags(
+            self.hide_dubs,
+            self.hide_rebroadcasts,
+            self.hide_instrumentals,
+            self.hide_chanting,
+            self.hide_character,
+        )
+    }
+}
+
+/// Animes with a title like the query, best matches first
+#[utoipa::path(
+    get,
+    path = "/search/anime",
+    params(SearchParams),
+    responses((status = 200, body = Vec<AnimeSearchResult>), (status = 400))
+)]
+pub async fn search_anime(
+    State(app_state): State<Arc<AppState>>,
+    Query(params): Query<SearchParams>,
+) -> Result<impl IntoResponse> {
+    if params.q.trim().is_empty() {
+        return Err(Error::InvalidParameter("Empty search".to_string()));
+    }
+
+    let animes = app_state
+        .database
+        .search_animes_by_title(
+            &params.q,
+            TITLE_SIMILARITY_LIMIT,
+            &params.filters(),
+            params
+                .limit
+                .unwrap_or(DEFAULT_RESULTS)
+                .clamp(1, MAX_RESULTS),
+        )
+        .await?;
+
+    Ok(Json(
+        animes
+            .into_iter()
+            .map(|(anime, similarity)| AnimeSearchResult::from_db_anime(anime, similarity))
+            .collect::<Vec<AnimeSearchResult>>(),
+    ))
+}
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::HeaderMap,
+    response::IntoResponse,
+};
+use axum_sessions::async_session::chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+use utoipa::IntoParams;
+
+use crate::{
+    AppState, Result,
+    auth::caller_user_id,
+    types::{ApiScope, ListeningStats},
+};
+
+const DEFAULT_TOP_SIZE: i64 = 10;
+const MAX_TOP_SIZE: i64 = 100;
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsParams {
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    limit: Option<i64>,
+}
+
+/// What the user listened to the most, from their listening history
+#[utoipa::path(
+    get,
+    path = "/me/stats",
+    params(StatsParams),
+    responses((status = 200, body = ListeningStats), (status = 401))
+)]
+pub async fn stats(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    session: Session,
+    Query(params): Query<StatsParams>,
+) -> Result<impl IntoResponse> {
+    let user_id = caller_user_id(&headers, &session, &app_state, ApiScope::History).await?;
+
+    Ok(Json(
+        app_state
+            .database
+            .get_listening_stats(
+                &user_id,
+                params.from,
+                params.to,
+                params
+                    .limit
+                    .unwrap_or(DEFAULT_TOP_SIZE)
+                    .clamp(1, MAX_TOP_SIZE),
+            )
+            .await?,
+    ))
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::login::{TOKEN_GRANT_SCOPE, authorize_url, new_state};
+use crate::{
+    AppState, Error, Result,
+    auth::generate_token,
+    spotify::auth::SpotifyAuth,
+    types::{ApiScope, ApiToken, NewApiToken},
+};
+
+const MAX_NAME_LENGTH: usize = 100;
+/// Session key of the (state, token id) of the spotify login being made for an API token
+pub(super) const TOKEN_GRANT_KEY: &str = "token_grant";
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewTokenParams {
+    name: String,
+    scopes: Vec<ApiScope>,
+}
+
+/// Makes a personal API token, only from the cookie session so tokens can't make more tokens
+#[utoipa::path(
+    post,
+    path = "/me/tokens",
+    request_body = NewTokenParams,
+    responses((status = 201, body = NewApiToken), (status = 400), (status = 401))
+)]
+pub async fn create_api_token(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Json(params): Json<NewTokenParams>,
+) -> Result<impl IntoResponse> {
+    let user_id = auth.user_id().await?;
+
+    let name = params.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(Error::InvalidParameter(format!(
+            "name must be between 1 and {} characters",
+            MAX_NAME_LENGTH
+        )));
+    }
+    if params.scopes.is_empty() {
+        return Err(Error::InvalidParameter(
+            "at least one scope is needed".to_string(),
+        ));
+    }
+
+    let (token, token_hash) = generate_token();
+    let info = app_state
+        .database
+        .create_api_token(&user_id, &name, &token_hash, &params.scopes)
+        .await?;
+
+    // Identifying has to ask spotify what the user is playing long after this session is gone.
+    // Sharing the sessions refresh token breaks one of them whenever spotify rotates it,
+    // so the token gets a spotify login of its own once the user approves it
+    let authorize_url = if params.scopes.contains(&ApiScope::Identify) {
+        let state = new_state();
+        auth.session
+            .insert(TOKEN_GRANT_KEY, (state.clone(), info.id))
+            .await?;
+        Some(authorize_url(
+            &app_state,
+            state,
+            TOKEN_GRANT_SCOPE.to_string(),
+        ))
+    } else {
+        None
+    };
+
+    Ok((
+        StatusCode::CREATED,
+        Json(NewApiToken {
+            token,
+            authorize_url,
+            info,
+        }),
+    ))
+}
+
+/// The users API tokens, without the tokens themselves
+#[utoipa::path(
+    get,
+    path = "/me/tokens",
+    responses((status = 200, body = Vec<ApiToken>), (status = 401))
+)]
+pub async fn api_tokens(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+) -> Result<impl IntoResponse> {
+    let user_id = auth.user_id().await?;
+
+    Ok(Json(app_state.database.get_api_tokens(&user_id).await?))
+}
+
+/// Stops an API token from working
+#[utoipa::path(
+    delete,
+    path = "/me/tokens/{token_id}",
+    params(("token_id" = i32, Path, description = "Id of the API token")),
+    responses((status = 204), (status = 404))
+)]
+pub async fn revoke_api_token(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Path(token_id): Path<i32>,
+) -> Result<impl IntoResponse> {
+    let user_id = auth.user_id().await?;
+
+    if !app_state
+        .database
+        .revoke_api_token(&user_id, token_id)
+        .await?
+    {
+        return Err(Error::NotFound);
+    }
+    app_state.api_token_cache.lock().unwrap().remove(&token_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
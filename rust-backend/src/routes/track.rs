@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+use utoipa::IntoParams;
+
+use super::lists::caller_watch_list;
+use crate::{
+    AppState, Error, Result,
+    auth::caller_user_id,
+    database::Database,
+    spotify::{api::get_song, links::SpotifyLink},
+    types::{ApiScope, NewSong},
+};
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TrackParams {
+    // Put shows on the callers linked anime lists first, ignored when not logged in
+    watched_first: Option<bool>,
+}
+
+/// Matches any track by id or link, no spotify login needed so shared links can be looked up by anyone
+pub async fn track(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    session: Session,
+    Path(spotify_id): Path<String>,
+    Query(params): Query<TrackParams>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(
+        identify_track(&app_state, &headers, &session, &spotify_id, &params).await?,
+    ))
+}
+
+pub(super) async fn identify_track(
+    app_state: &Arc<AppState>,
+    headers: &HeaderMap,
+    session: &Session,
+    link_or_id: &str,
+    params: &TrackParams,
+) -> Result<NewSong> {
+    let spotify_id = SpotifyLink::track_id(link_or_id).ok_or(Error::InvalidParameter(format!(
+        "Not a spotify track: {}",
+        link_or_id
+    )))?;
+
+    // Refused rather than queued, a client hammering this would only make the queue longer
+    let _permit = app_state
+        .public_track_lookups
+        .try_acquire()
+        .map_err(|_| Error::TooManyRequests)?;
+
+    let token = app_state.client_token.access_token().await?;
+    let song = match get_song(spotify_id, token).await {
+        // Spotify answers 400 for ids that can't exist and 404 for ones that don't
+        Err(Error::BadRequest { status_code, .. }) if status_code.is_client_error() => {
+            return Err(Error::NotFound);
+        }
+        song => song?,
+    };
+
+    let mut new_song = app_state
+        .database
+        .get_anime_2(&song, &app_state.anisong_db, Database::ACCURACY_CUTOFF)
+        .await?;
+
+    // Anyone may look tracks up, those logged in also see what they have watched
+    if let Ok(user_id) = caller_user_id(headers, session, app_state, ApiScope::Identify).await {
+        if let Some(watch_list) = caller_watch_list(app_state, &user_id).await {
+            new_song.annotate_watch_status(
+                &watch_list,
+                params.watched_first.is_some_and(|value| value),
+            );
+        }
+    }
+    Ok(new_song)
+}
@@ -1,112 +1,194 @@
 use std::{
     sync::Arc,
-    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant},
 };
 
 use axum::{
-    Json,
     extract::{Query, State},
+    http::HeaderMap,
     response::IntoResponse,
 };
 use log::warn;
 use serde::{Deserialize, Serialize};
 use tower_sessions::Session;
+use utoipa::IntoParams;
 
+use super::lists::caller_watch_list;
 use crate::{
     AppState,
+    auth::{caller_access_token, caller_user_id},
+    database::Database,
     error::{Error, Result},
     spotify::{
-        api::{currently_playing, refresh_access_token},
+        api::currently_playing,
         responses::{CurrentlyPlayingResponses, Item},
     },
-    types::ContentUpdate,
+    types::{
+        AnimeType, ApiScope, ContentUpdate, NewSong, SongFilters, SongHit, SortKey, TrackType,
+    },
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+async fn record_listen(
+    app_state: &Arc<AppState>,
+    user_id: &String,
+    spotify_id: &String,
+    hit: &SongHit,
+) -> Result<()> {
+    let ann_song_ids = hit.anime_info.iter().map(|a| a.ann_song_id).collect();
+
+    app_state
+        .database
+        .add_listen(user_id, spotify_id, &ann_song_ids, hit.certainty)
+        .await
+}
+
+#[derive(Debug, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct UpdateParams {
     refresh: Option<bool>,
+    // Put shows on the users linked anime lists first
+    watched_first: Option<bool>,
+    hide_dubs: Option<bool>,
+    hide_rebroadcasts: Option<bool>,
+    hide_instrumentals: Option<bool>,
+    hide_chanting: Option<bool>,
+    hide_character: Option<bool>,
+    track_type: Option<TrackType>,
+    anime_type: Option<AnimeType>,
+    year_min: Option<i32>,
+    year_max: Option<i32>,
+    min_score: Option<i32>,
+    sort: Option<SortKey>,
+    descending: Option<bool>,
+    group_by_franchise: Option<bool>,
+}
+
+impl UpdateParams {
+    fn filters(&self) -> SongFilters {
+        SongFilters {
+            track_type: self.track_type,
+            anime_type: self.anime_type,
+            year_min: self.year_min,
+            year_max: self.year_max,
+            min_score: self.min_score,
+            ..SongFilters::from_flags(
+                self.hide_dubs,
+                self.hide_rebroadcasts,
+                self.hide_instrumentals,
+                self.hide_chanting,
+                self.hide_character,
+            )
+        }
+    }
 }
 
 pub async fn update(
     State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
     session: Session,
     Query(params): Query<UpdateParams>,
 ) -> Result<impl IntoResponse> {
+    content_update(&app_state, &headers, &session, &params).await
+}
+
+/// Looks up what the user is playing right now, shared by the legacy and versioned update routes
+pub(super) async fn content_update(
+    app_state: &Arc<AppState>,
+    headers: &HeaderMap,
+    session: &Session,
+    params: &UpdateParams,
+) -> Result<ContentUpdate> {
     session.load().await.unwrap();
 
-    let token_option = session.get::<String>("access_token").await.unwrap();
+    let token = match caller_access_token(headers, session, app_state, ApiScope::Identify).await {
+        Ok(token) => token,
+        Err(Error::BadOAuth) => return Ok(ContentUpdate::LoginRequired),
+        Err(error) => return Err(error),
+    };
+    let current_song_response = currently_playing(&token).await?;
 
-    match token_option {
-        Some(_) => {
-            let expire_time_option = session.get::<u64>("expire_time").await.unwrap().unwrap();
-            if expire_time_option
-                < SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
+    let current_song = match current_song_response {
+        CurrentlyPlayingResponses::Playing(value) => value,
+        CurrentlyPlayingResponses::NotPlaying => {
+            session.insert("previously_played", "").await.unwrap();
+            return Ok(ContentUpdate::NotPlaying);
+        }
+        CurrentlyPlayingResponses::BadToken => {
+            return Ok(ContentUpdate::LoginRequired);
+        }
+        CurrentlyPlayingResponses::Ratelimited => {
+            return Ok(ContentUpdate::NotPlaying);
+        }
+        CurrentlyPlayingResponses::SpotifyError(status_code) => {
+            warn!("Spotify return error code: {}", status_code);
+            return Ok(ContentUpdate::NoUpdates);
+        }
+        CurrentlyPlayingResponses::BadOAuth => {
+            warn!("Likely unnapproved user");
+            return Ok(ContentUpdate::UnnapprovedUser);
+        }
+    };
+
+    match current_song.item {
+        Item::TrackObject(song) => {
+            if params.refresh.is_none_or(|value| !value)
+                && session
+                    .get::<String>("previously_played")
+                    .await
                     .unwrap()
-                    .as_secs()
+                    .is_some_and(|value| value == song.id)
             {
-                refresh_access_token(session.clone(), app_state.clone())
-                    .await
-                    .unwrap();
+                return Ok(ContentUpdate::NoUpdates);
             }
-            let current_song_response = currently_playing(session.clone()).await.unwrap();
+            session.insert("previously_played", &song.id).await.unwrap();
+            let start = Instant::now();
+            let mut new_song = app_state
+                .database
+                .get_anime_2(&song, &app_state.anisong_db, Database::ACCURACY_CUTOFF)
+                .await?;
+            let duration = start.elapsed();
 
-            let current_song = match current_song_response {
-                CurrentlyPlayingResponses::Playing(value) => value,
-                CurrentlyPlayingResponses::NotPlaying => {
-                    session.insert("previously_played", "").await.unwrap();
-                    return Ok(Json(ContentUpdate::NotPlaying));
-                }
-                CurrentlyPlayingResponses::BadToken => {
-                    return Ok(Json(ContentUpdate::LoginRequired));
-                }
-                CurrentlyPlayingResponses::Ratelimited => {
-                    return Ok(Json(ContentUpdate::NotPlaying));
-                }
-                CurrentlyPlayingResponses::SpotifyError(status_code) => {
-                    warn!("Spotify return error code: {}", status_code);
-                    return Ok(Json(ContentUpdate::NoUpdates));
-                }
-                CurrentlyPlayingResponses::BadOAuth => {
-                    warn!("Likely unnapproved user");
-                    return Ok(Json(ContentUpdate::UnnapprovedUser));
-                }
-            };
-
-            match current_song.item {
-                Item::TrackObject(song) => {
-                    if params.refresh.is_none_or(|value| !value)
-                        && session
-                            .get::<String>("previously_played")
-                            .await
-                            .unwrap()
-                            .is_some_and(|value| value == song.id)
-                    {
-                        return Ok(Json(ContentUpdate::NoUpdates));
+            let user_id =
+                match caller_user_id(headers, session, app_state, ApiScope::Identify).await {
+                    Ok(user_id) => Some(user_id),
+                    Err(error) => {
+                        warn!("Failed to get user id: {:?}", error);
+                        None
                     }
-                    session.insert("previously_played", &song.id).await.unwrap();
-                    let start = Instant::now();
-                    let value = Ok(Json(ContentUpdate::NewSong(
-                        app_state
-                            .database
-                            .get_anime_2(&song, &app_state.anisong_db, 40.0)
-                            .await
-                            .unwrap(),
-                    )));
-                    let duration = start.elapsed();
-                    if duration > Duration::from_secs(1) {
-                        warn!(
-                            "Time to find animes: {:?} song: {},\nlink: https://open.spotify.com/track/{}",
-                            duration, &song.name, &song.id
-                        );
-                    }
-                    value
+                };
+
+            if let (NewSong::Hit(hit), Some(user_id)) = (&new_song, &user_id) {
+                if let Err(error) = record_listen(app_state, user_id, &song.id, hit).await {
+                    warn!("Failed to save listen of {}: {:?}", &song.id, error);
                 }
-                _ => Err(Error::NotASong),
             }
+
+            new_song.apply_filters(&params.filters());
+            if let Some(key) = params.sort {
+                new_song.sort(key, params.descending.is_some_and(|value| value));
+            }
+
+            if let Some(user_id) = &user_id {
+                if let Some(watch_list) = caller_watch_list(app_state, user_id).await {
+                    new_song.annotate_watch_status(
+                        &watch_list,
+                        params.watched_first.is_some_and(|value| value),
+                    );
+                }
+            }
+
+            if params.group_by_franchise.is_some_and(|value| value) {
+                new_song.group_by_franchise();
+            }
+
+            if duration > Duration::from_secs(1) {
+                warn!(
+                    "Time to find animes: {:?} song: {},\nlink: https://open.spotify.com/track/{}",
+                    duration, &song.name, &song.id
+                );
+            }
+            Ok(ContentUpdate::NewSong(new_song))
         }
-        None => {
-            return Ok(Json(ContentUpdate::LoginRequired));
-        }
+        _ => Err(Error::NotASong),
     }
 }
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::Response,
+    routing::{delete, get, post},
+};
+use tower_sessions::Session;
+use utoipa::OpenApi;
+
+use super::{
+    analyze_playlist, anime_by_ann_id, anime_by_mal_id, answer_quiz, api_tokens, artist,
+    artist_links,
+    confirm_anime::{self, ConfirmationParams, ConfirmationResult},
+    create_api_token, history, import_mal_list, import_status, library, link_anilist, logout,
+    playlist, quiz_results,
+    report::{self, ReportParams},
+    revoke_api_token, search_anime, start_import, start_quiz, stats,
+    track::{TrackParams, identify_track},
+    update::{self, UpdateParams},
+};
+use crate::{
+    AppState, Result,
+    spotify::auth::SpotifyAuth,
+    types::{ContentUpdate, FrontendAnimeEntry, SongHit, SongMiss, TAGGED_INDEXES, UpdateResponse},
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "WhatAnime API", version = "1"),
+    servers((url = "/api/v1")),
+    paths(
+        openapi,
+        super::logout::logout,
+        update,
+        track,
+        confirm_anime,
+        report,
+        super::anime::anime_by_ann_id,
+        super::anime::anime_by_mal_id,
+        super::artist::artist,
+        super::artist::artist_links,
+        super::search::search_anime,
+        super::playlist::playlist,
+        super::analyze::analyze_playlist,
+        super::history::history,
+        super::stats::stats,
+        super::import::start_import,
+        super::import::import_status,
+        super::import::library,
+        super::lists::link_anilist,
+        super::lists::import_mal_list,
+        super::tokens::create_api_token,
+        super::tokens::api_tokens,
+        super::tokens::revoke_api_token,
+        super::quiz::start_quiz,
+        super::quiz::quiz_results,
+        super::quiz::answer_quiz
+    ),
+    components(schemas(
+        UpdateResponse,
+        SongHit,
+        SongMiss,
+        FrontendAnimeEntry,
+        ConfirmationParams,
+        ConfirmationResult,
+        ReportParams
+    ))
+)]
+struct ApiDoc;
+
+/// The versioned API, mounted at /api/v1. The unversioned routes stay around for older clients
+pub fn api_v1() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/openapi.json", get(openapi))
+        .route("/logout", post(logout))
+        .route("/update", get(update))
+        .route("/confirm_anime", post(confirm_anime))
+        .route("/report", post(report))
+        .route("/anime/{ann_id}", get(anime_by_ann_id))
+        .route("/anime/by-mal/{mal_id}", get(anime_by_mal_id))
+        .route("/artist/{ann_id}", get(artist))
+        .route("/artist_links", get(artist_links))
+        .route("/track/{spotify_id}", get(track))
+        .route("/search/anime", get(search_anime))
+        .route("/playlist", post(playlist))
+        .route("/analyze/playlist", post(analyze_playlist))
+        .route("/me/history", get(history))
+        .route("/me/stats", get(stats))
+        .route("/me/import", post(start_import))
+        .route("/me/import/{job_id}", get(import_status))
+        .route("/me/library", get(library))
+        .route("/me/lists/anilist", post(link_anilist))
+        .route("/me/lists/mal", post(import_mal_list))
+        .route("/me/tokens", get(api_tokens).post(create_api_token))
+        .route("/me/tokens/{token_id}", delete(revoke_api_token))
+        .route("/quiz", post(start_quiz))
+        .route("/quiz/{quiz_id}", get(quiz_results))
+        .route("/quiz/{quiz_id}/answer", post(answer_quiz))
+        .layer(middleware::from_fn(tag_indexes))
+}
+
+/// Anime and track indexes come as {"kind": "Season", "number": 2} in this version
+async fn tag_indexes(request: Request, next: Next) -> Response {
+    TAGGED_INDEXES.scope((), next.run(request)).await
+}
+
+/// The OpenAPI document for this version of the API
+#[utoipa::path(get, path = "/openapi.json", responses((status = 200, description = "OpenAPI document")))]
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// What the user is currently playing and which animes it is from
+#[utoipa::path(
+    get,
+    path = "/update",
+    params(UpdateParams),
+    responses((status = 200, body = UpdateResponse))
+)]
+async fn update(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    session: Session,
+    Query(params): Query<UpdateParams>,
+) -> Result<Json<UpdateResponse>> {
+    let update = update::content_update(&app_state, &headers, &session, &params).await?;
+    Ok(Json(update.into()))
+}
+
+/// Matches any track by id, open.spotify.com link or spotify: uri, no login needed
+#[utoipa::path(
+    get,
+    path = "/track/{spotify_id}",
+    params(("spotify_id" = String, Path, description = "Track id, link or uri"), TrackParams),
+    responses((status = 200, body = UpdateResponse), (status = 404), (status = 429))
+)]
+async fn track(
+    State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    session: Session,
+    Path(spotify_id): Path<String>,
+    Query(params): Query<TrackParams>,
+) -> Result<Json<UpdateResponse>> {
+    let new_song = identify_track(&app_state, &headers, &session, &spotify_id, &params).await?;
+    Ok(Json(ContentUpdate::NewSong(new_song).into()))
+}
+
+/// Links a track to the anime songs the user picked
+#[utoipa::path(
+    post,
+    path = "/confirm_anime",
+    request_body = ConfirmationParams,
+    responses((status = 200, body = ConfirmationResult))
+)]
+async fn confirm_anime(
+    State(app_state): State<Arc<AppState>>,
+    auth: SpotifyAuth,
+    Json(params): Json<ConfirmationParams>,
+) -> Result<Json<ConfirmationResult>> {
+    let result = confirm_anime::add_confirmed_anime(&app_state, &auth, params).await?;
+    Ok(Json(result))
+}
+
+/// Reports a wrong match between a track and an anime song
+#[utoipa::path(
+    post,
+    path = "/report",
+    request_body = ReportParams,
+    responses((status = 204))
+)]
+async fn report(
+    state: State<Arc<AppState>>,
+    auth: Option<SpotifyAuth>,
+    params: Json<ReportParams>,
+) -> Result<StatusCode> {
+    report::report(state, auth, params).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,91 +1,295 @@
 use crate::{AppState, Error, Result};
 
-use super::responses::{CurrentlyPlayingResponses, SpotifyToken, SpotifyUser, TrackObject};
+use super::responses::{
+    ArtistObject, CurrentlyPlayingResponses, Paging, PlaylistTrack, SeveralArtists, SeveralTracks,
+    SimplifiedTrack, SpotifyPlaylist, SpotifyToken, SpotifyUser, TrackObject,
+};
 use base64::{Engine, engine};
 use log::{error, warn};
 use reqwest::Client;
 use reqwest::header::{HeaderMap, HeaderValue};
+use serde::de::DeserializeOwned;
+use serde_json::json;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tower_sessions::Session;
 
+// Get a new token a bit before the old one expires so it can't expire mid request
+pub const EXPIRY_MARGIN: u64 = 60;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
 pub async fn refresh_access_token(session: Session, app_state: Arc<AppState>) -> Result<()> {
+    let refresh_token: String = session.get("refresh_token").await?.ok_or(Error::BadOAuth)?;
+    let pkce = session.get::<bool>("pkce").await?.unwrap_or(false);
+    let refreshed = app_state
+        .session_refreshes
+        .refresh(&refresh_token, pkce, &app_state)
+        .await?;
+
+    session
+        .insert("access_token", refreshed.access_token)
+        .await?;
+    session.insert("expire_time", refreshed.expire_time).await?;
+    // Spotify keeps the old refresh token working unless it sends a new one
+    if let Some(refresh_token) = refreshed.refresh_token {
+        session.insert("refresh_token", refresh_token).await?;
+    }
+    session.save().await?;
+    return Ok(());
+}
+
+/// What refreshing a session token gave, with when the access token expires in seconds since the epoch
+#[derive(Clone)]
+struct RefreshedToken {
+    access_token: String,
+    expire_time: u64,
+    refresh_token: Option<String>,
+}
+
+/// Session token refreshes by the refresh token they used. Requests of the same session that all see
+/// the token expiring wait for one refresh and share its result, instead of racing each other with a
+/// refresh token the first of them might have used up
+#[derive(Default)]
+pub struct SessionRefreshes {
+    refreshes: std::sync::Mutex<HashMap<String, Arc<tokio::sync::Mutex<Option<RefreshedToken>>>>>,
+}
+
+impl SessionRefreshes {
+    async fn refresh(
+        &self,
+        refresh_token: &String,
+        pkce: bool,
+        app_state: &AppState,
+    ) -> Result<RefreshedToken> {
+        let refresh = self
+            .refreshes
+            .lock()
+            .unwrap()
+            .entry(refresh_token.clone())
+            .or_default()
+            .clone();
+
+        let refreshed = {
+            let mut refreshed = refresh.lock().await;
+            match refreshed.as_ref() {
+                Some(refreshed) => refreshed.clone(),
+                None => {
+                    let token_info =
+                        match request_refreshed_token(refresh_token, pkce, app_state).await {
+                            // The refresh token was revoked or has expired, the user has to log in again
+                            Err(Error::BadRequest { status_code, .. })
+                                if status_code.is_client_error() =>
+                            {
+                                return Err(Error::BadOAuth);
+                            }
+                            token_info => token_info?,
+                        };
+                    let token = RefreshedToken {
+                        access_token: token_info.access_token,
+                        expire_time: now() + token_info.expires_in,
+                        refresh_token: token_info.refresh_token,
+                    };
+                    *refreshed = Some(token.clone());
+                    token
+                }
+            }
+        };
+
+        // Results are only needed by requests that started before the refresh finished, so drop
+        // them once their access token is no longer usable
+        let now = now();
+        self.refreshes
+            .lock()
+            .unwrap()
+            .retain(|_, refresh| match refresh.try_lock() {
+                Ok(refreshed) => refreshed
+                    .as_ref()
+                    .is_some_and(|refreshed| refreshed.expire_time > now + EXPIRY_MARGIN),
+                Err(_) => true,
+            });
+        Ok(refreshed)
+    }
+}
+
+/// Trades the code from a login for tokens, the redirect_uri has to be the one the login used.
+/// Logins that used PKCE send their code_verifier instead of our client secret
+pub async fn request_authorization_token(
+    code: String,
+    redirect_uri: &str,
+    code_verifier: Option<String>,
+    app_state: &AppState,
+) -> Result<SpotifyToken> {
+    let mut token_data = HashMap::from([
+        ("grant_type", "authorization_code".to_string()),
+        ("code", code),
+        ("redirect_uri", redirect_uri.to_string()),
+    ]);
+    match code_verifier {
+        Some(code_verifier) => {
+            token_data.insert("code_verifier", code_verifier);
+            request_token(&token_data, &app_state.client_id, None).await
+        }
+        None => {
+            request_token(
+                &token_data,
+                &app_state.client_id,
+                Some(&app_state.client_secret),
+            )
+            .await
+        }
+    }
+}
+
+/// Trades a refresh token for a new access token, spotify only sometimes sends a new refresh token along.
+/// Tokens from a PKCE login have to be refreshed without the client secret
+pub async fn request_refreshed_token(
+    refresh_token: &String,
+    pkce: bool,
+    app_state: &AppState,
+) -> Result<SpotifyToken> {
     let token_data = HashMap::from([
         ("grant_type", "refresh_token".to_string()),
-        (
-            "refresh_token",
-            session.get("refresh_token").await?.unwrap(),
-        ),
-        // ("client_id", app_state.client_id.clone()), only for PKCE extension or something
+        ("refresh_token", refresh_token.clone()),
     ]);
+    let client_secret = (!pkce).then_some(&app_state.client_secret);
+    request_token(&token_data, &app_state.client_id, client_secret).await
+}
 
-    let refresh_url = "https://accounts.spotify.com/api/token";
-    let mut headers = HeaderMap::new();
+/// Keeps the client credentials token of our app, which is enough for catalog lookups like
+/// tracks and artists and doesn't need anyone to be logged in
+pub struct ClientTokenManager {
+    client_id: String,
+    client_secret: String,
+    // The token and when it expires, in seconds since the epoch
+    token: tokio::sync::Mutex<Option<(String, u64)>>,
+}
+
+impl ClientTokenManager {
+    pub fn new(client_id: String, client_secret: String) -> Self {
+        Self {
+            client_id,
+            client_secret,
+            token: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    pub async fn access_token(&self) -> Result<String> {
+        // Held while fetching so concurrent lookups wait for one new token instead of each getting one
+        let mut token = self.token.lock().await;
+        let now = now();
+
+        if let Some((access_token, expire_time)) = token.as_ref() {
+            if *expire_time > now + EXPIRY_MARGIN {
+                return Ok(access_token.clone());
+            }
+        }
+
+        let token_data = HashMap::from([("grant_type", "client_credentials".to_string())]);
+        let token_info =
+            request_token(&token_data, &self.client_id, Some(&self.client_secret)).await?;
+        *token = Some((token_info.access_token.clone(), now + token_info.expires_in));
+        Ok(token_info.access_token)
+    }
+}
 
-    let client_creds = format!("{}:{}", app_state.client_id, app_state.client_secret);
-    let client_creds_b64 = engine::general_purpose::STANDARD.encode(client_creds);
+/// Public clients (PKCE) identify with just the client id in the body, we use the client secret otherwise
+async fn request_token(
+    token_data: &HashMap<&str, String>,
+    client_id: &String,
+    client_secret: Option<&String>,
+) -> Result<SpotifyToken> {
+    let token_url = "https://accounts.spotify.com/api/token";
+    let mut headers = HeaderMap::new();
+    let mut token_data = token_data.clone();
 
     headers.insert(
         "Content-Type",
         HeaderValue::from_static("application/x-www-form-urlencoded"),
     );
-    headers.insert(
-        "Authorization",
-        HeaderValue::from_str(&format!("Basic {}", client_creds_b64)).unwrap(),
-    );
+    match client_secret {
+        Some(client_secret) => {
+            let client_creds = format!("{}:{}", client_id, client_secret);
+            let client_creds_b64 = engine::general_purpose::STANDARD.encode(client_creds);
+            headers.insert(
+                "Authorization",
+                HeaderValue::from_str(&format!("Basic {}", client_creds_b64)).unwrap(),
+            );
+        }
+        None => {
+            token_data.insert("client_id", client_id.clone());
+        }
+    }
 
     let token_response = Client::new()
-        .post(refresh_url)
+        .post(token_url)
         .headers(headers)
         .form(&token_data)
         .send()
-        .await
-        .unwrap();
+        .await?;
 
     let token_info: SpotifyToken = match token_response.status() {
-        status if status.is_success() => token_response.json().await.unwrap(),
+        status if status.is_success() => token_response.json().await?,
         status => {
             error!(
                 "Spotify returned error code: {} response text:\n{}",
                 status,
-                token_response.text().await.unwrap()
+                token_response.text().await.unwrap_or_default()
             );
             return Err(Error::BadRequest {
-                url: refresh_url.to_string(),
+                url: token_url.to_string(),
                 status_code: status,
             });
         }
     };
+    Ok(token_info)
+}
 
-    if token_info.refresh_token.is_some() {
-        session
-            .insert("access_token", token_info.access_token)
-            .await?;
-        session
-            .insert(
-                "expire_time",
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs()
-                    + token_info.expires_in,
-            )
-            .await?;
-        session
-            .insert("refresh_token", token_info.refresh_token.unwrap())
-            .await?;
+/// The access token of the session, refreshed first if it is about to expire
+pub async fn session_access_token(session: &Session, app_state: Arc<AppState>) -> Result<String> {
+    let expire_time = session
+        .get::<u64>("expire_time")
+        .await?
+        .ok_or(Error::BadOAuth)?;
+
+    if expire_time < now() + EXPIRY_MARGIN {
+        refresh_access_token(session.clone(), app_state).await?;
     }
-    return Ok(());
-}
 
-pub async fn currently_playing(session: Session) -> Result<CurrentlyPlayingResponses> {
-    let access_token = session
+    session
         .get::<String>("access_token")
-        .await
-        .unwrap()
-        .unwrap();
+        .await?
+        .ok_or(Error::BadOAuth)
+}
 
+/// Fails with MissingScope unless the user granted the scope when logging in
+pub async fn require_scope(session: &Session, scope: &str) -> Result<()> {
+    let granted = session.get::<String>("scope").await?.unwrap_or_default();
+    if granted.split(' ').any(|s| s == scope) {
+        Ok(())
+    } else {
+        Err(Error::MissingScope(scope.to_string()))
+    }
+}
+
+/// The spotify user id of the session, only asked from spotify the first time
+pub async fn session_user_id(session: &Session, token: &String) -> Result<String> {
+    if let Some(user_id) = session.get::<String>("user_id").await? {
+        return Ok(user_id);
+    }
+
+    let user = get_user(token.clone()).await?;
+    session.insert("user_id", &user.id).await?;
+    Ok(user.id)
+}
+
+pub async fn currently_playing(access_token: &String) -> Result<CurrentlyPlayingResponses> {
     let mut headers = HeaderMap::new();
     headers.insert(
         "Authorization",
@@ -97,15 +301,14 @@ pub async fn currently_playing(session: Session) -> Result<CurrentlyPlayingRespo
         .get(currently_playing_url)
         .headers(headers)
         .send()
-        .await
-        .unwrap();
+        .await?;
 
     match response.status() {
         // Note to self, make sure not parsable success cases are before the if status.is_success()
         axum::http::StatusCode::NO_CONTENT => Ok(CurrentlyPlayingResponses::NotPlaying),
-        status if status.is_success() => Ok(CurrentlyPlayingResponses::Playing(
-            response.json().await.unwrap(),
-        )),
+        status if status.is_success() => {
+            Ok(CurrentlyPlayingResponses::Playing(response.json().await?))
+        }
         axum::http::StatusCode::UNAUTHORIZED => Ok(CurrentlyPlayingResponses::BadToken),
         axum::http::StatusCode::FORBIDDEN => Ok(CurrentlyPlayingResponses::BadOAuth),
         axum::http::StatusCode::TOO_MANY_REQUESTS => Ok(CurrentlyPlayingResponses::Ratelimited),
@@ -113,7 +316,7 @@ pub async fn currently_playing(session: Session) -> Result<CurrentlyPlayingRespo
             warn!(
                 "Spotify returned code {}, response text:\n{}",
                 status,
-                response.text().await.unwrap(),
+                response.text().await.unwrap_or_default(),
             );
             Ok(CurrentlyPlayingResponses::SpotifyError(status)) // Might want to implement better logic here
         }
@@ -121,7 +324,7 @@ pub async fn currently_playing(session: Session) -> Result<CurrentlyPlayingRespo
             error!(
                 "Spotify returned unhandled code {}, response text:\n{}",
                 status,
-                response.text().await.unwrap(),
+                response.text().await.unwrap_or_default(),
             );
             Err(Error::BadRequest {
                 url: currently_playing_url.to_string(),
@@ -139,19 +342,196 @@ pub async fn get_song(spotify_id: String, token: String) -> Result<TrackObject>
         .get(&url)
         .header("Authorization", format!("Bearer {}", token))
         .send()
-        .await
-        .unwrap();
+        .await?;
 
     match response.status() {
-        status if status.is_success() => Ok(response.json().await.unwrap()),
+        status if status.is_success() => Ok(response.json().await?),
         status => {
             error!(
                 "Spotify returned error code: {} response text:\n{}",
                 status,
-                response.text().await.unwrap()
+                response.text().await.unwrap_or_default()
             );
             Err(Error::BadRequest {
-                url: url,
+                url,
+                status_code: status,
+            })
+        }
+    }
+}
+
+/// Fetches every item of a paged endpoint by following next, stopping after max_items
+async fn get_all_pages<T: DeserializeOwned>(
+    first_url: String,
+    token: &String,
+    max_items: usize,
+) -> Result<Vec<T>> {
+    let mut items = Vec::new();
+    let mut next = Some(first_url);
+
+    while let Some(url) = next {
+        let response = Client::new()
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .send()
+            .await?;
+
+        let page: Paging<T> = match response.status() {
+            status if status.is_success() => response.json().await?,
+            status => {
+                error!(
+                    "Spotify returned error code: {} response text:\n{}",
+                    status,
+                    response.text().await.unwrap_or_default()
+                );
+                return Err(Error::BadRequest {
+                    url,
+                    status_code: status,
+                });
+            }
+        };
+
+        items.extend(page.items);
+        if items.len() >= max_items {
+            items.truncate(max_items);
+            break;
+        }
+        next = page.next;
+    }
+    Ok(items)
+}
+
+/// The tracks of a playlist, None for items that are not parsable spotify tracks like episodes and local files
+pub async fn get_playlist_tracks(
+    playlist_id: &String,
+    token: &String,
+    max_tracks: usize,
+) -> Result<Vec<Option<TrackObject>>> {
+    let url = format!(
+        "https://api.spotify.com/v1/playlists/{}/tracks?limit=100",
+        playlist_id
+    );
+    let items: Vec<PlaylistTrack> = get_all_pages(url, token, max_tracks).await?;
+
+    Ok(items
+        .into_iter()
+        .map(|item| {
+            item.track
+                .and_then(|track| serde_json::from_value::<TrackObject>(track).ok())
+        })
+        .collect())
+}
+
+/// Up to the last 50 tracks the user played, newest first
+pub async fn get_recently_played(token: &String) -> Result<Vec<Option<TrackObject>>> {
+    // Spotify doesn't keep more than the last 50 plays
+    const MAX_RECENTLY_PLAYED: usize = 50;
+
+    let url = "https://api.spotify.com/v1/me/player/recently-played?limit=50".to_string();
+    let items: Vec<PlaylistTrack> = get_all_pages(url, token, MAX_RECENTLY_PLAYED).await?;
+
+    Ok(items
+        .into_iter()
+        .map(|item| {
+            item.track
+                .and_then(|track| serde_json::from_value::<TrackObject>(track).ok())
+        })
+        .collect())
+}
+
+/// The tracks in the users liked songs, most recently saved first
+pub async fn get_saved_tracks(
+    token: &String,
+    max_tracks: usize,
+) -> Result<Vec<Option<TrackObject>>> {
+    let url = "https://api.spotify.com/v1/me/tracks?limit=50".to_string();
+    let items: Vec<PlaylistTrack> = get_all_pages(url, token, max_tracks).await?;
+
+    Ok(items
+        .into_iter()
+        .map(|item| {
+            item.track
+                .and_then(|track| serde_json::from_value::<TrackObject>(track).ok())
+        })
+        .collect())
+}
+
+/// The tracks of an album, the album endpoint only gives simplified tracks so these are fetched again in full
+pub async fn get_album_tracks(
+    album_id: &String,
+    token: &String,
+    max_tracks: usize,
+) -> Result<Vec<Option<TrackObject>>> {
+    const MAX_IDS_PER_REQUEST: usize = 50;
+
+    let url = format!(
+        "https://api.spotify.com/v1/albums/{}/tracks?limit=50",
+        album_id
+    );
+    let simplified: Vec<SimplifiedTrack> = get_all_pages(url, token, max_tracks).await?;
+    let ids: Vec<String> = simplified.into_iter().filter_map(|t| t.id).collect();
+
+    let mut tracks = Vec::with_capacity(ids.len());
+    for chunk in ids.chunks(MAX_IDS_PER_REQUEST) {
+        tracks.extend(get_songs(chunk, token).await?);
+    }
+    Ok(tracks)
+}
+
+pub async fn get_songs(spotify_ids: &[String], token: &String) -> Result<Vec<Option<TrackObject>>> {
+    let url = format!(
+        "https://api.spotify.com/v1/tracks?ids={}",
+        spotify_ids.join(",")
+    );
+
+    let response = Client::new()
+        .get(&url)
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await?;
+
+    match response.status() {
+        status if status.is_success() => Ok(response.json::<SeveralTracks>().await?.tracks),
+        status => {
+            error!(
+                "Spotify returned error code: {} response text:\n{}",
+                status,
+                response.text().await.unwrap_or_default()
+            );
+            Err(Error::BadRequest {
+                url,
+                status_code: status,
+            })
+        }
+    }
+}
+
+/// Full artist objects, at most 50 ids per call. None for ids spotify doesn't know
+pub async fn get_artists(
+    spotify_ids: &[String],
+    token: &String,
+) -> Result<Vec<Option<ArtistObject>>> {
+    let url = format!(
+        "https://api.spotify.com/v1/artists?ids={}",
+        spotify_ids.join(",")
+    );
+
+    let response = Client::new()
+        .get(&url)
+        .header("Authorization", format!("Bearer {}", token))
+        .send()
+        .await?;
+
+    match response.status() {
+        status if status.is_success() => Ok(response.json::<SeveralArtists>().await?.artists),
+        status => {
+            error!(
+                "Spotify returned error code: {} response text:\n{}",
+                status,
+                response.text().await.unwrap_or_default()
+            );
+            Err(Error::BadRequest {
+                url,
                 status_code: status,
             })
         }
@@ -165,16 +545,15 @@ pub async fn get_user(token: String) -> Result<SpotifyUser> {
         .get(url)
         .header("Authorization", format!("Bearer {}", &token))
         .send()
-        .await
-        .unwrap();
+        .await?;
 
     match response.status() {
-        status if status.is_success() => Ok(response.json().await.unwrap()),
+        status if status.is_success() => Ok(response.json().await?),
         status => {
             error!(
                 "Spotify returned error code: {} response text:\n{}",
                 status,
-                response.text().await.unwrap()
+                response.text().await.unwrap_or_default()
             );
             Err(Error::BadRequest {
                 url: url.to_string(),
@@ -183,3 +562,105 @@ pub async fn get_user(token: String) -> Result<SpotifyUser> {
         }
     }
 }
+
+pub async fn create_playlist(
+    token: &String,
+    user_id: &String,
+    name: &String,
+    description: &String,
+) -> Result<SpotifyPlaylist> {
+    let url = format!("https://api.spotify.com/v1/users/{}/playlists", user_id);
+
+    let response = Client::new()
+        .post(&url)
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&json!({
+            "name": name,
+            "description": description,
+            "public": false,
+        }))
+        .send()
+        .await?;
+
+    match response.status() {
+        status if status.is_success() => Ok(response.json().await?),
+        status => {
+            error!(
+                "Spotify returned error code: {} response text:\n{}",
+                status,
+                response.text().await.unwrap_or_default()
+            );
+            Err(Error::BadRequest {
+                url,
+                status_code: status,
+            })
+        }
+    }
+}
+
+/// Replaces everything in a playlist with the given tracks, returns None if the playlist no longer exists
+pub async fn replace_playlist_tracks(
+    token: &String,
+    playlist_id: &String,
+    spotify_ids: &Vec<String>,
+) -> Result<Option<()>> {
+    // Spotify allows at most 100 tracks per request, the first request replaces and the rest append
+    const MAX_TRACKS_PER_REQUEST: usize = 100;
+    let url = format!(
+        "https://api.spotify.com/v1/playlists/{}/tracks",
+        playlist_id
+    );
+
+    let uris: Vec<String> = spotify_ids
+        .iter()
+        .map(|id| format!("spotify:track:{}", id))
+        .collect();
+    let mut chunks = uris.chunks(MAX_TRACKS_PER_REQUEST);
+
+    let first = chunks.next().unwrap_or(&[]);
+    let response = Client::new()
+        .put(&url)
+        .header("Authorization", format!("Bearer {}", token))
+        .json(&json!({ "uris": first }))
+        .send()
+        .await?;
+
+    match response.status() {
+        status if status.is_success() => {}
+        axum::http::StatusCode::NOT_FOUND => return Ok(None),
+        status => {
+            error!(
+                "Spotify returned error code: {} response text:\n{}",
+                status,
+                response.text().await.unwrap_or_default()
+            );
+            return Err(Error::BadRequest {
+                url,
+                status_code: status,
+            });
+        }
+    }
+
+    for chunk in chunks {
+        let response = Client::new()
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .json(&json!({ "uris": chunk }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            error!(
+                "Spotify returned error code: {} response text:\n{}",
+                status,
+                response.text().await.unwrap_or_default()
+            );
+            return Err(Error::BadRequest {
+                url,
+                status_code: status,
+            });
+        }
+    }
+    Ok(Some(()))
+}
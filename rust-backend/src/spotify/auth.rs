@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{FromRequestParts, OptionalFromRequestParts},
+    http::request::Parts,
+};
+use tower_sessions::Session;
+
+use super::api::{require_scope, session_access_token, session_user_id};
+use crate::{AppState, Error, Result};
+
+/// The spotify login of the cookie session, refreshed first when it has expired.
+/// Rejects with 401 when nobody is logged in, use Option<SpotifyAuth> for routes that work logged out
+pub struct SpotifyAuth {
+    pub access_token: String,
+    pub session: Session,
+}
+
+impl SpotifyAuth {
+    pub async fn user_id(&self) -> Result<String> {
+        session_user_id(&self.session, &self.access_token).await
+    }
+
+    pub async fn require_scope(&self, scope: &str) -> Result<()> {
+        require_scope(&self.session, scope).await
+    }
+}
+
+impl FromRequestParts<Arc<AppState>> for SpotifyAuth {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Self> {
+        let session = Session::from_request_parts(parts, state)
+            .await
+            .map_err(|_| Error::BadOAuth)?;
+        let access_token = session_access_token(&session, state.clone()).await?;
+        Ok(Self {
+            access_token,
+            session,
+        })
+    }
+}
+
+impl OptionalFromRequestParts<Arc<AppState>> for SpotifyAuth {
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AppState>) -> Result<Option<Self>> {
+        match <Self as FromRequestParts<Arc<AppState>>>::from_request_parts(parts, state).await {
+            Ok(auth) => Ok(Some(auth)),
+            Err(Error::BadOAuth) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+}
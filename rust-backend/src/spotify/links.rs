@@ -0,0 +1,108 @@
+use url::Url;
+
+/// Something a user can paste from spotify, either an open.spotify.com link or a spotify: uri
+#[derive(Debug, PartialEq)]
+pub enum SpotifyLink {
+    Album(String),
+    Playlist(String),
+    Track(String),
+}
+
+impl SpotifyLink {
+    pub fn parse(link: &str) -> Option<Self> {
+        let link = link.trim();
+        // Declared out here as the kind and id borrow from it
+        let url;
+        let (kind, id) = match link.strip_prefix("spotify:") {
+            Some(uri) => uri.split_once(':')?,
+            None => {
+                url = Url::parse(link).ok()?;
+                if url.host_str() != Some("open.spotify.com") {
+                    return None;
+                }
+                // Localized links look like open.spotify.com/intl-ja/track/{id}
+                let mut segments = url
+                    .path_segments()?
+                    .filter(|s| !s.is_empty() && !s.starts_with("intl-"));
+                (segments.next()?, segments.next()?)
+            }
+        };
+
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+
+        match kind {
+            "album" => Some(Self::Album(id.to_string())),
+            "playlist" => Some(Self::Playlist(id.to_string())),
+            "track" => Some(Self::Track(id.to_string())),
+            _ => None,
+        }
+    }
+
+    /// The id of a track link, or the input itself when it already is a bare track id
+    pub fn track_id(input: &str) -> Option<String> {
+        let input = input.trim();
+        match Self::parse(input) {
+            Some(Self::Track(id)) => Some(id),
+            Some(_) => None,
+            None if !input.is_empty() && input.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                Some(input.to_string())
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_urls_and_uris() {
+        assert_eq!(
+            SpotifyLink::parse("https://open.spotify.com/playlist/37i9dQZF1DX6XceWZP1znY?si=abc"),
+            Some(SpotifyLink::Playlist("37i9dQZF1DX6XceWZP1znY".to_string()))
+        );
+        assert_eq!(
+            SpotifyLink::parse("https://open.spotify.com/intl-ja/album/4yP0hdKOZPNshxUOjY0cZj"),
+            Some(SpotifyLink::Album("4yP0hdKOZPNshxUOjY0cZj".to_string()))
+        );
+        assert_eq!(
+            SpotifyLink::parse("spotify:album:4yP0hdKOZPNshxUOjY0cZj"),
+            Some(SpotifyLink::Album("4yP0hdKOZPNshxUOjY0cZj".to_string()))
+        );
+        assert_eq!(
+            SpotifyLink::parse("https://open.spotify.com/track/2QjOHCTQ1Jl3zawyYOpxh6?si=xyz"),
+            Some(SpotifyLink::Track("2QjOHCTQ1Jl3zawyYOpxh6".to_string()))
+        );
+    }
+
+    #[test]
+    fn finds_track_ids() {
+        assert_eq!(
+            SpotifyLink::track_id("spotify:track:2QjOHCTQ1Jl3zawyYOpxh6"),
+            Some("2QjOHCTQ1Jl3zawyYOpxh6".to_string())
+        );
+        assert_eq!(
+            SpotifyLink::track_id("https://open.spotify.com/intl-de/track/2QjOHCTQ1Jl3zawyYOpxh6"),
+            Some("2QjOHCTQ1Jl3zawyYOpxh6".to_string())
+        );
+        assert_eq!(
+            SpotifyLink::track_id("2QjOHCTQ1Jl3zawyYOpxh6"),
+            Some("2QjOHCTQ1Jl3zawyYOpxh6".to_string())
+        );
+        assert_eq!(
+            SpotifyLink::track_id("https://open.spotify.com/album/4yP0hdKOZPNshxUOjY0cZj"),
+            None
+        );
+        assert_eq!(SpotifyLink::track_id("../tracks"), None);
+    }
+
+    #[test]
+    fn rejects_other_links() {
+        assert_eq!(SpotifyLink::parse("https://example.com/playlist/abc"), None);
+        assert_eq!(SpotifyLink::parse("spotify:show:abc"), None);
+        assert_eq!(SpotifyLink::parse("not a link"), None);
+    }
+}
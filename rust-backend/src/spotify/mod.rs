@@ -1,2 +1,4 @@
 pub mod responses;
-pub mod api;
\ No newline at end of file
+pub mod api;
+pub mod auth;
+pub mod links;
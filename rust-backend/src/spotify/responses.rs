@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 pub struct SpotifyToken {
     pub access_token: String,
     // pub token_type: String,
-    // pub scope: String,
+    pub scope: Option<String>,
     pub expires_in: u64,
     pub refresh_token: Option<String>,
 }
@@ -183,3 +183,63 @@ pub struct SpotifyUser {
     pub email: Option<String>,
     pub id: String,
 }
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+pub struct SpotifyPlaylist {
+    pub id: String,
+    pub name: String,
+    pub external_urls: ExternalUrls,
+}
+
+#[derive(Deserialize)]
+pub struct Paging<T> {
+    pub items: Vec<T>,
+    pub next: Option<String>,
+}
+
+/// An item of a playlist, the saved tracks or the play history
+#[derive(Deserialize)]
+pub struct PlaylistTrack {
+    // Tracks, episodes and local files, which we parse one by one so one odd item doesn't fail the page
+    pub track: Option<serde_json::Value>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+pub struct SimplifiedTrack {
+    pub id: Option<String>,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct SeveralTracks {
+    pub tracks: Vec<Option<TrackObject>>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+pub struct Followers {
+    pub href: Option<String>,
+    pub total: u32,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+pub struct ArtistObject {
+    pub external_urls: ExternalUrls,
+    pub followers: Followers,
+    pub genres: Vec<String>,
+    pub href: String,
+    pub id: String,
+    pub images: Vec<Image>,
+    pub name: String,
+    pub popularity: u32,
+    pub r#type: String,
+    pub uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct SeveralArtists {
+    pub artists: Vec<Option<ArtistObject>>,
+}
@@ -1,15 +1,42 @@
-use std::str::FromStr;
+use std::{cmp::Ordering, collections::HashMap, str::FromStr};
 
 use crate::{
     Error, Result,
-    anilist::{Media, types::ImageURL},
+    anilist::{
+        Media,
+        types::{ImageURL, ReleaseSeason},
+    },
     anisong::{Anime, AnimeListLinks},
     database::databasetypes::DBAnime,
     spotify::responses::TrackObject,
 };
 use axum::response::IntoResponse;
-use serde::{Deserialize, Serialize};
-#[derive(Serialize)]
+use axum_sessions::async_session::chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+use sqlx::{FromRow, Type};
+use utoipa::ToSchema;
+
+tokio::task_local! {
+    /// Set while /api/v1 answers, which tags indexes with kind and number. The unversioned routes
+    /// keep the {"Season": 2} encoding older clients read
+    pub static TAGGED_INDEXES: ();
+}
+
+/// Serializes an index the way the route answering expects, see TAGGED_INDEXES
+fn serialize_index<S: Serializer, T: Serialize, N: Serialize>(
+    index: &T,
+    name: &'static str,
+    discriminant: u8,
+    kind: &'static str,
+    number: N,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match TAGGED_INDEXES.try_with(|_| ()) {
+        Ok(()) => index.serialize(serializer),
+        Err(_) => serializer.serialize_newtype_variant(name, discriminant as u32, kind, &number),
+    }
+}
+#[derive(Serialize, ToSchema)]
 pub struct SongInfo {
     pub title: String,
     pub artists: Vec<String>,
@@ -26,7 +53,13 @@ impl SongInfo {
                 .iter()
                 .map(|a| a.name.clone())
                 .collect(),
-            album_picture_url: track_object.album.images[0].url.clone(),
+            // Some tracks, mostly local uploads, come without any album art
+            album_picture_url: track_object
+                .album
+                .images
+                .first()
+                .map(|image| image.url.clone())
+                .unwrap_or_default(),
             spotify_id: track_object.id.clone(),
         }
     }
@@ -43,7 +76,7 @@ fn split_string<T: FromStr>(input: &str) -> (String, Option<T>) {
     }
     (input.to_owned(), None)
 }
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
 pub enum AnimeType {
     TV,
     Movie,
@@ -81,7 +114,8 @@ impl AnimeType {
         }
     }
 }
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "kind", content = "number")]
 #[repr(u8)]
 pub enum AnimeTrackIndex {
     Opening(i32),
@@ -114,6 +148,26 @@ impl AnimeTrackIndex {
             | AnimeTrackIndex::Ending(val) => *val,
         }
     }
+    fn kind(&self) -> &'static str {
+        match self {
+            AnimeTrackIndex::Opening(_) => "Opening",
+            AnimeTrackIndex::Insert(_) => "Insert",
+            AnimeTrackIndex::Ending(_) => "Ending",
+        }
+    }
+    fn serialize_for_route<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serialize_index(
+            self,
+            "AnimeTrackIndex",
+            self.discriminant(),
+            self.kind(),
+            self.value(),
+            serializer,
+        )
+    }
     pub fn from_db(discriminator: i16, value: i32) -> Result<Self> {
         match discriminator {
             0 => Ok(AnimeTrackIndex::Opening(value)),
@@ -124,7 +178,103 @@ impl AnimeTrackIndex {
     }
 }
 
-#[derive(Serialize)]
+/// The kind of an AnimeTrackIndex without its number, used for filtering
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[repr(i16)]
+pub enum TrackType {
+    Opening = 0,
+    Insert = 1,
+    Ending = 2,
+}
+
+impl TrackType {
+    pub fn discriminant(&self) -> i16 {
+        *self as i16
+    }
+}
+
+/// Where a user is with an anime according to their AniList or MyAnimeList,
+/// ordered so that sorting by it puts watched shows first
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Type, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[repr(i16)]
+pub enum WatchStatus {
+    Completed = 0,
+    Watching = 1,
+    Paused = 2,
+    Dropped = 3,
+    Planning = 4,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Type)]
+#[serde(rename_all = "snake_case")]
+#[repr(i16)]
+pub enum ListSource {
+    Anilist = 0,
+    MyAnimeList = 1,
+}
+
+/// A users watch statuses, looked up by either anime list id
+#[derive(Default)]
+pub struct WatchList {
+    pub by_anilist: HashMap<i32, WatchStatus>,
+    pub by_mal: HashMap<i32, WatchStatus>,
+}
+
+impl WatchList {
+    pub fn status_of(&self, links: &AnimeListLinks) -> Option<WatchStatus> {
+        links
+            .anilist
+            .and_then(|id| self.by_anilist.get(&id.0))
+            .or_else(|| links.myanimelist.and_then(|id| self.by_mal.get(&id)))
+            .copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_anilist.is_empty() && self.by_mal.is_empty()
+    }
+
+    /// Marks every anime with where the user is with it, optionally moving watched shows first
+    pub fn annotate(&self, animes: &mut Vec<FrontendAnimeEntry>, watched_first: bool) {
+        for anime in animes.iter_mut() {
+            anime.watch_status = self.status_of(&anime.linked_ids);
+        }
+        if watched_first {
+            animes.sort_by_key(|a| a.watch_status.map_or(i16::MAX, |s| s as i16));
+        }
+    }
+}
+
+/// Where in a users spotify library an import takes its tracks from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Type, ToSchema)]
+#[serde(rename_all = "snake_case")]
+#[repr(i16)]
+pub enum ImportSource {
+    RecentlyPlayed = 0,
+    SavedTracks = 1,
+}
+
+/// What a personal API token may do for its user
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    // Ask what the user is playing and match it
+    Identify,
+    // Read the listening history and stats
+    History,
+}
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::Identify => "identify",
+            ApiScope::History => "history",
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "kind", content = "number")]
 #[repr(u8)]
 pub enum AnimeIndex {
     Season(f32),
@@ -153,6 +303,32 @@ impl AnimeIndex {
             | AnimeIndex::MusicVideo(val) => *val,
         }
     }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            AnimeIndex::Season(_) => "Season",
+            AnimeIndex::Movie(_) => "Movie",
+            AnimeIndex::ONA(_) => "ONA",
+            AnimeIndex::OVA(_) => "OVA",
+            AnimeIndex::TVSpecial(_) => "TVSpecial",
+            AnimeIndex::Special(_) => "Special",
+            AnimeIndex::MusicVideo(_) => "MusicVideo",
+        }
+    }
+
+    fn serialize_for_route<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serialize_index(
+            self,
+            "AnimeIndex",
+            self.discriminant(),
+            self.kind(),
+            self.value(),
+            serializer,
+        )
+    }
 }
 
 impl AnimeIndex {
@@ -189,22 +365,41 @@ impl AnimeIndex {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct FrontendAnimeEntry {
     pub title: String,
     pub title_japanese: String,
+    pub title_romaji: Option<String>,
+    pub title_native: Option<String>,
+    #[serde(serialize_with = "AnimeIndex::serialize_for_route")]
     pub anime_index: AnimeIndex,
+    #[serde(serialize_with = "AnimeTrackIndex::serialize_for_route")]
     pub track_index: AnimeTrackIndex,
     pub anime_type: Option<AnimeType>,
+    #[schema(value_type = Option<String>)]
     pub image_url: Option<ImageURL>,
+    #[schema(value_type = Option<String>)]
     pub banner_url: Option<ImageURL>,
     pub linked_ids: AnimeListLinks,
     pub score: Option<i32>,
+    pub release_year: Option<i32>,
+    pub franchise_id: Option<i32>,
+    pub watch_status: Option<WatchStatus>,
 
     pub ann_song_id: i32,
     pub song_name: String,
     pub artist_ids: Vec<i32>,
     pub artist_names: Vec<String>,
+
+    pub hq_url: Option<String>,
+    pub mq_url: Option<String>,
+    pub audio_url: Option<String>,
+    pub song_difficulty: Option<f64>,
+    pub song_length: Option<f64>,
+    pub song_category: Option<String>,
+    pub is_dub: Option<bool>,
+    pub is_rebroadcast: Option<bool>,
+    pub alt_titles: Option<Vec<String>>,
 }
 impl FrontendAnimeEntry {
     pub fn new(anisong_anime: &Anime, anilist_media: Option<&Media>) -> Result<Self> {
@@ -212,6 +407,8 @@ impl FrontendAnimeEntry {
         Ok(Self {
             title: anisong_anime.animeENName.clone(),
             title_japanese: anisong_anime.animeJPName.clone(),
+            title_romaji: anilist_media.and_then(|a| a.title.romaji.clone()),
+            title_native: anilist_media.and_then(|a| a.title.native.clone()),
             anime_index: AnimeIndex::from_str(&anisong_anime.animeCategory).unwrap(),
             track_index: AnimeTrackIndex::from_str(&anisong_anime.songType).unwrap(),
             anime_type: Some(anime_type),
@@ -230,13 +427,27 @@ impl FrontendAnimeEntry {
                 .map(|a| a.names[0].clone())
                 .collect(),
             score: anilist_media.map(|a| a.mean_score),
+            release_year: anilist_media.and_then(|a| a.season_year),
+            franchise_id: None,
+            watch_status: None,
             ann_song_id: anisong_anime.annSongId,
+            hq_url: anisong_anime.HQ.clone(),
+            mq_url: anisong_anime.MQ.clone(),
+            audio_url: anisong_anime.audio.clone(),
+            song_difficulty: anisong_anime.songDifficulty,
+            song_length: anisong_anime.songLength,
+            song_category: Some(anisong_anime.songCategory.clone()),
+            is_dub: Some(anisong_anime.isDub),
+            is_rebroadcast: Some(anisong_anime.isRebroadcast),
+            alt_titles: anisong_anime.animeAltName.clone(),
         })
     }
     pub fn from_db_anime(db_anime: &DBAnime) -> Self {
         Self {
             title: db_anime.title_eng.clone(),
             title_japanese: db_anime.title_jpn.clone(),
+            title_romaji: db_anime.title_romaji.clone(),
+            title_native: db_anime.title_native.clone(),
             anime_index: AnimeIndex::from_db(db_anime.index_type, db_anime.index_number).unwrap(),
             track_index: AnimeTrackIndex::from_db(
                 db_anime.track_index_type,
@@ -256,7 +467,19 @@ impl FrontendAnimeEntry {
             artist_ids: db_anime.artists_ann_id.clone(),
             artist_names: db_anime.artist_names.iter().map(|a| a.clone()).collect(),
             score: db_anime.mean_score,
+            release_year: db_anime.release_year,
+            franchise_id: db_anime.franchise_id,
+            watch_status: None,
             ann_song_id: db_anime.ann_song_id,
+            hq_url: db_anime.hq_url.clone(),
+            mq_url: db_anime.mq_url.clone(),
+            audio_url: db_anime.audio_url.clone(),
+            song_difficulty: db_anime.song_difficulty,
+            song_length: db_anime.song_length,
+            song_category: db_anime.song_category.clone(),
+            is_dub: db_anime.is_dub,
+            is_rebroadcast: db_anime.is_rebroadcast,
+            alt_titles: db_anime.alt_titles.clone(),
         }
     }
     pub fn from_db_animes(db_animes: &Vec<DBAnime>) -> Vec<FrontendAnimeEntry> {
@@ -282,8 +505,7 @@ impl FrontendAnimeEntry {
                 .map(|a| a.linked_ids.anilist.unwrap())
                 .collect(),
         )
-        .await
-        .unwrap();
+        .await?;
 
         owned_anisongs.sort_by_key(|a| a.linked_ids.anilist);
         anilist_animes.sort_by(|a, b| a.id.cmp(&b.id));
@@ -320,14 +542,81 @@ impl FrontendAnimeEntry {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+pub struct AnimeSong {
+    pub anime_info: FrontendAnimeEntry,
+    pub spotify_ids: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ArtistSummary {
+    pub ann_id: i32,
+    pub names: Vec<String>,
+}
+
+/// An artist as spotify knows it, only the name is known until the artists endpoint has been asked
+#[derive(Serialize, FromRow, Clone, ToSchema)]
+pub struct SpotifyArtist {
+    pub spotify_id: String,
+    pub name: String,
+    pub image_url: Option<String>,
+    pub genres: Vec<String>,
+    pub popularity: Option<i32>,
+    pub followers: Option<i32>,
+}
+
+/// One link between an anisong artist and a spotify artist, with what made us link them
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct ArtistLinkAudit {
+    pub ann_id: i32,
+    pub names: Vec<String>,
+    pub spotify_id: String,
+    pub spotify_name: Option<String>,
+    pub score: Option<f32>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// One song, as in one song_group, and every anime it appears in
+#[derive(Serialize, ToSchema)]
+pub struct ArtistSong {
+    pub song_group_id: Option<i32>,
+    pub song_name: String,
+    pub artist_ids: Vec<i32>,
+    pub artist_names: Vec<String>,
+    pub spotify_ids: Vec<String>,
+    pub animes: Vec<FrontendAnimeEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ArtistDiscography {
+    pub ann_id: i32,
+    pub names: Vec<String>,
+    pub groups: Vec<ArtistSummary>,
+    pub members: Vec<ArtistSummary>,
+    pub spotify_ids: Vec<String>,
+    pub spotify_artists: Vec<SpotifyArtist>,
+    pub songs: Vec<ArtistSong>,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct SongHit {
     pub song_info: SongInfo,
     pub certainty: i32,
     pub anime_info: Vec<FrontendAnimeEntry>,
     pub more_with_artist: Vec<FrontendAnimeEntry>,
+    // Only when asked for, anime_info is then moved into these groups
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub franchises: Option<Vec<FranchiseGroup>>,
 }
-#[derive(Serialize)]
+
+/// The hits that belong to one franchise, seasons in order
+#[derive(Serialize, ToSchema)]
+pub struct FranchiseGroup {
+    pub franchise_id: Option<i32>,
+    pub title: String,
+    pub animes: Vec<FrontendAnimeEntry>,
+}
+#[derive(Serialize, ToSchema)]
 pub struct SongMiss {
     pub song_info: SongInfo,
     pub possible_anime: Vec<FrontendAnimeEntry>,
@@ -337,6 +626,427 @@ pub enum NewSong {
     Hit(SongHit),
     Miss(SongMiss),
 }
+
+/// Which songs a client wants in results, everything is shown by default
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SongFilters {
+    pub hide_dubs: bool,
+    pub hide_rebroadcasts: bool,
+    pub hide_instrumentals: bool,
+    pub hide_chanting: bool,
+    pub hide_character: bool,
+    pub track_type: Option<TrackType>,
+    pub anime_type: Option<AnimeType>,
+    pub year_min: Option<i32>,
+    pub year_max: Option<i32>,
+    pub min_score: Option<i32>,
+}
+
+impl SongFilters {
+    /// Filters from the hide flags clients send as query parameters, a missing flag shows everything
+    pub fn from_flags(
+        hide_dubs: Option<bool>,
+        hide_rebroadcasts: Option<bool>,
+        hide_instrumentals: Option<bool>,
+        hide_chanting: Option<bool>,
+        hide_character: Option<bool>,
+    ) -> Self {
+        SongFilters {
+            hide_dubs: hide_dubs.unwrap_or(false),
+            hide_rebroadcasts: hide_rebroadcasts.unwrap_or(false),
+            hide_instrumentals: hide_instrumentals.unwrap_or(false),
+            hide_chanting: hide_chanting.unwrap_or(false),
+            hide_character: hide_character.unwrap_or(false),
+            ..Default::default()
+        }
+    }
+
+    /// The anisong song categories that are hidden, compared against song_category
+    pub fn hidden_categories(&self) -> Vec<&'static str> {
+        [
+            (self.hide_instrumentals, "Instrumental"),
+            (self.hide_chanting, "Chanting"),
+            (self.hide_character, "Character"),
+        ]
+        .into_iter()
+        .filter_map(|(hidden, category)| hidden.then_some(category))
+        .collect()
+    }
+
+    pub fn allows(&self, anime: &FrontendAnimeEntry) -> bool {
+        let hidden = self.hide_dubs && anime.is_dub == Some(true)
+            || self.hide_rebroadcasts && anime.is_rebroadcast == Some(true)
+            || anime
+                .song_category
+                .as_ref()
+                .is_some_and(|c| self.hidden_categories().contains(&c.as_str()));
+
+        // Entries missing the filtered value don't pass the filter
+        !hidden
+            && self
+                .track_type
+                .is_none_or(|t| anime.track_index.discriminant() as i16 == t.discriminant())
+            && self.anime_type.is_none_or(|t| anime.anime_type == Some(t))
+            && self
+                .year_min
+                .is_none_or(|min| anime.release_year.is_some_and(|year| year >= min))
+            && self
+                .year_max
+                .is_none_or(|max| anime.release_year.is_some_and(|year| year <= max))
+            && self
+                .min_score
+                .is_none_or(|min| anime.score.is_some_and(|score| score >= min))
+    }
+}
+
+/// What to order anime entries by, entries without the value go last
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    ReleaseYear,
+    Score,
+    TrackIndex,
+    AnimeIndex,
+}
+
+impl SortKey {
+    fn is_missing(&self, anime: &FrontendAnimeEntry) -> bool {
+        match self {
+            SortKey::ReleaseYear => anime.release_year.is_none(),
+            SortKey::Score => anime.score.is_none(),
+            SortKey::TrackIndex | SortKey::AnimeIndex => false,
+        }
+    }
+
+    fn compare(&self, a: &FrontendAnimeEntry, b: &FrontendAnimeEntry) -> Ordering {
+        match self {
+            SortKey::ReleaseYear => a.release_year.cmp(&b.release_year),
+            SortKey::Score => a.score.cmp(&b.score),
+            SortKey::TrackIndex => (a.track_index.discriminant(), a.track_index.value())
+                .cmp(&(b.track_index.discriminant(), b.track_index.value())),
+            SortKey::AnimeIndex => (a.anime_index.discriminant(), a.anime_index.value())
+                .partial_cmp(&(b.anime_index.discriminant(), b.anime_index.value()))
+                .unwrap_or(Ordering::Equal),
+        }
+    }
+
+    pub fn sort(&self, animes: &mut Vec<FrontendAnimeEntry>, descending: bool) {
+        animes.sort_by(|a, b| match (self.is_missing(a), self.is_missing(b)) {
+            (false, true) => Ordering::Less,
+            (true, false) => Ordering::Greater,
+            (true, true) => Ordering::Equal,
+            (false, false) if descending => self.compare(b, a),
+            (false, false) => self.compare(a, b),
+        });
+    }
+}
+
+impl NewSong {
+    /// Moves the hits into groups by franchise, keeping the order the franchises first appear in.
+    /// Animes we don't know the franchise of get a group of their own.
+    pub fn group_by_franchise(&mut self) {
+        if let NewSong::Hit(hit) = self {
+            let mut groups: Vec<FranchiseGroup> = Vec::new();
+            for anime in hit.anime_info.drain(..) {
+                let franchise_id = anime.franchise_id;
+                match groups
+                    .iter_mut()
+                    .find(|g| franchise_id.is_some() && g.franchise_id == franchise_id)
+                {
+                    Some(group) => group.animes.push(anime),
+                    None => groups.push(FranchiseGroup {
+                        franchise_id,
+                        title: anime.title.clone(),
+                        animes: vec![anime],
+                    }),
+                }
+            }
+            for group in groups.iter_mut() {
+                SortKey::AnimeIndex.sort(&mut group.animes, false);
+                group.title = group.animes[0].title.clone();
+            }
+            hit.franchises = Some(groups);
+        }
+    }
+
+    pub fn sort(&mut self, key: SortKey, descending: bool) {
+        match self {
+            NewSong::Hit(hit) => {
+                key.sort(&mut hit.anime_info, descending);
+                key.sort(&mut hit.more_with_artist, descending);
+            }
+            NewSong::Miss(miss) => key.sort(&mut miss.possible_anime, descending),
+        }
+    }
+
+    /// Removes hidden songs, except that a hit always keeps the song that was matched
+    pub fn apply_filters(&mut self, filters: &SongFilters) {
+        match self {
+            NewSong::Hit(hit) => {
+                if hit.anime_info.iter().any(|a| filters.allows(a)) {
+                    hit.anime_info.retain(|a| filters.allows(a));
+                }
+                hit.more_with_artist.retain(|a| filters.allows(a));
+            }
+            NewSong::Miss(miss) => miss.possible_anime.retain(|a| filters.allows(a)),
+        }
+    }
+
+    pub fn annotate_watch_status(&mut self, watch_list: &WatchList, watched_first: bool) {
+        match self {
+            NewSong::Hit(hit) => {
+                watch_list.annotate(&mut hit.anime_info, watched_first);
+                watch_list.annotate(&mut hit.more_with_artist, watched_first);
+            }
+            NewSong::Miss(miss) => watch_list.annotate(&mut miss.possible_anime, watched_first),
+        }
+    }
+}
+/// An anime found by title search, with the song fields left out since any of its songs could have matched
+#[derive(Serialize, ToSchema)]
+pub struct AnimeSearchResult {
+    pub ann_id: i32,
+    pub title: String,
+    pub title_japanese: String,
+    pub title_romaji: Option<String>,
+    pub title_native: Option<String>,
+    pub alt_titles: Option<Vec<String>>,
+    pub anime_type: Option<AnimeType>,
+    #[schema(value_type = Option<String>)]
+    pub image_url: Option<ImageURL>,
+    pub linked_ids: AnimeListLinks,
+    pub similarity: f32,
+}
+
+impl AnimeSearchResult {
+    pub fn from_db_anime(db_anime: DBAnime, similarity: f32) -> Self {
+        Self {
+            ann_id: db_anime.ann_id,
+            anime_type: AnimeType::from_db(db_anime.anime_type).ok(),
+            linked_ids: AnimeListLinks {
+                myanimelist: db_anime.mal_id,
+                anidb: db_anime.anidb_id,
+                anilist: db_anime.anilist_id,
+                kitsu: db_anime.kitsu_id,
+            },
+            title: db_anime.title_eng,
+            title_japanese: db_anime.title_jpn,
+            title_romaji: db_anime.title_romaji,
+            title_native: db_anime.title_native,
+            alt_titles: db_anime.alt_titles,
+            image_url: db_anime.cover_image_medium,
+            similarity,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct QuizQuestion {
+    pub position: i32,
+    pub audio: String,
+    pub spotify_id: Option<String>,
+    pub difficulty: Option<f64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Quiz {
+    pub quiz_id: i32,
+    pub questions: Vec<QuizQuestion>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct QuizAnswerResult {
+    pub correct: bool,
+    pub score: f32,
+    pub anime_info: Option<FrontendAnimeEntry>,
+}
+
+/// A question of a finished or ongoing quiz, the anime is only revealed once it has been answered
+#[derive(Serialize, ToSchema)]
+pub struct QuizQuestionResult {
+    pub position: i32,
+    pub answer: Option<String>,
+    pub score: Option<f32>,
+    pub correct: Option<bool>,
+    pub anime_info: Option<FrontendAnimeEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct QuizResults {
+    pub quiz_id: i32,
+    pub total: usize,
+    pub answered: usize,
+    pub correct: usize,
+    pub questions: Vec<QuizQuestionResult>,
+}
+
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct ImportJob {
+    pub id: i32,
+    #[serde(skip)]
+    pub spotify_user_id: String,
+    pub source: ImportSource,
+    pub total: i32,
+    pub processed: i32,
+    pub hits: i32,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct ApiToken {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A freshly made token, the only time the token itself is shown
+#[derive(Serialize, ToSchema)]
+pub struct NewApiToken {
+    pub token: String,
+    // Where the user approves the spotify access of an identify token, it can't identify until then
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorize_url: Option<String>,
+    #[serde(flatten)]
+    pub info: ApiToken,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LibraryTrack {
+    pub spotify_id: String,
+    pub source: ImportSource,
+    pub certainty: i32,
+    pub imported_at: DateTime<Utc>,
+    pub anime_info: Vec<FrontendAnimeEntry>,
+}
+
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct TopAnime {
+    pub ann_id: i32,
+    pub title: String,
+    pub title_japanese: String,
+    #[schema(value_type = Option<String>)]
+    pub image_url: Option<ImageURL>,
+    pub plays: i64,
+}
+
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct TopArtist {
+    pub ann_id: i32,
+    pub names: Vec<String>,
+    pub plays: i64,
+}
+
+/// Plays of a genre or studio
+#[derive(Serialize, FromRow, ToSchema)]
+pub struct NamedCount {
+    pub name: String,
+    pub plays: i64,
+}
+
+#[derive(Serialize, FromRow, Default, ToSchema)]
+pub struct TrackTypeCounts {
+    pub opening: i64,
+    pub insert: i64,
+    pub ending: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SeasonCount {
+    pub year: Option<i32>,
+    #[schema(value_type = Option<String>)]
+    pub season: Option<ReleaseSeason>,
+    pub plays: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ListeningStats {
+    pub total_plays: i64,
+    pub top_anime: Vec<TopAnime>,
+    pub top_artists: Vec<TopArtist>,
+    pub track_types: TrackTypeCounts,
+    pub genres: Vec<NamedCount>,
+    pub studios: Vec<NamedCount>,
+    pub seasons: Vec<SeasonCount>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HistoryEntry {
+    pub spotify_id: String,
+    pub certainty: i32,
+    pub listened_at: DateTime<Utc>,
+    pub anime_info: Vec<FrontendAnimeEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    pub page: i64,
+    pub total: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TrackReport {
+    Hit {
+        song_info: SongInfo,
+        certainty: i32,
+        anime_info: Vec<FrontendAnimeEntry>,
+    },
+    Miss {
+        song_info: SongInfo,
+    },
+    Error {
+        title: Option<String>,
+        reason: String,
+    },
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AnalysisSummary {
+    pub total: usize,
+    pub hits: usize,
+    pub misses: usize,
+    pub errors: usize,
+    pub average_certainty: f32,
+}
+
+impl AnalysisSummary {
+    pub fn from_reports(reports: &Vec<TrackReport>) -> Self {
+        let certainties: Vec<i32> = reports
+            .iter()
+            .filter_map(|r| match r {
+                TrackReport::Hit { certainty, .. } => Some(*certainty),
+                _ => None,
+            })
+            .collect();
+        let errors = reports
+            .iter()
+            .filter(|r| matches!(r, TrackReport::Error { .. }))
+            .count();
+        Self {
+            total: reports.len(),
+            hits: certainties.len(),
+            misses: reports.len() - certainties.len() - errors,
+            errors,
+            average_certainty: match certainties.len() {
+                0 => 0.0,
+                hits => certainties.iter().sum::<i32>() as f32 / hits as f32,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PlaylistAnalysis {
+    pub tracks: Vec<TrackReport>,
+    pub summary: AnalysisSummary,
+}
+
 #[derive(Serialize)]
 pub enum ContentUpdate {
     NewSong(NewSong),
@@ -348,8 +1058,32 @@ pub enum ContentUpdate {
 
 impl IntoResponse for ContentUpdate {
     fn into_response(self) -> axum::response::Response {
-        let json = serde_json::to_string(&self).unwrap();
-        axum::response::Json(json).into_response()
+        axum::response::Json(self).into_response()
+    }
+}
+
+/// What /api/v1/update returns, tagged with a "type" field instead of wrapping the payload
+#[derive(Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UpdateResponse {
+    Hit(SongHit),
+    Miss(SongMiss),
+    LoginRequired,
+    NoUpdates,
+    NotPlaying,
+    UnapprovedUser,
+}
+
+impl From<ContentUpdate> for UpdateResponse {
+    fn from(update: ContentUpdate) -> Self {
+        match update {
+            ContentUpdate::NewSong(NewSong::Hit(hit)) => Self::Hit(hit),
+            ContentUpdate::NewSong(NewSong::Miss(miss)) => Self::Miss(miss),
+            ContentUpdate::LoginRequired => Self::LoginRequired,
+            ContentUpdate::NoUpdates => Self::NoUpdates,
+            ContentUpdate::NotPlaying => Self::NotPlaying,
+            ContentUpdate::UnnapprovedUser => Self::UnapprovedUser,
+        }
     }
 }
 
@@ -495,3 +1229,201 @@ pub struct Genre {
     pub name: String,
     pub url: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(ann_song_id: i32, anime_index: AnimeIndex) -> FrontendAnimeEntry {
+        FrontendAnimeEntry {
+            title: format!("Anime {}", ann_song_id),
+            title_japanese: String::new(),
+            title_romaji: None,
+            title_native: None,
+            anime_index,
+            track_index: AnimeTrackIndex::Opening(1),
+            anime_type: Some(AnimeType::TV),
+            image_url: None,
+            banner_url: None,
+            linked_ids: AnimeListLinks {
+                myanimelist: None,
+                anidb: None,
+                anilist: None,
+                kitsu: None,
+            },
+            score: None,
+            release_year: None,
+            franchise_id: None,
+            watch_status: None,
+            ann_song_id,
+            song_name: String::new(),
+            artist_ids: vec![],
+            artist_names: vec![],
+            hq_url: None,
+            mq_url: None,
+            audio_url: None,
+            song_difficulty: None,
+            song_length: None,
+            song_category: None,
+            is_dub: None,
+            is_rebroadcast: None,
+            alt_titles: None,
+        }
+    }
+
+    fn ids(animes: &[FrontendAnimeEntry]) -> Vec<i32> {
+        animes.iter().map(|a| a.ann_song_id).collect()
+    }
+
+    #[test]
+    fn filters_hidden_songs() {
+        let dub = FrontendAnimeEntry {
+            is_dub: Some(true),
+            ..entry(1, AnimeIndex::Season(1.0))
+        };
+        let instrumental = FrontendAnimeEntry {
+            song_category: Some("Instrumental".to_string()),
+            ..entry(2, AnimeIndex::Season(1.0))
+        };
+        let ending = FrontendAnimeEntry {
+            track_index: AnimeTrackIndex::Ending(1),
+            ..entry(3, AnimeIndex::Season(1.0))
+        };
+
+        let everything = SongFilters::default();
+        assert!(everything.allows(&dub));
+        assert!(everything.allows(&instrumental));
+
+        let filters = SongFilters::from_flags(Some(true), None, Some(true), None, None);
+        assert!(!filters.allows(&dub));
+        assert!(!filters.allows(&instrumental));
+        assert!(filters.allows(&ending));
+
+        let openings = SongFilters {
+            track_type: Some(TrackType::Opening),
+            ..Default::default()
+        };
+        assert!(openings.allows(&dub));
+        assert!(!openings.allows(&ending));
+    }
+
+    #[test]
+    fn filters_out_missing_values() {
+        let unknown = entry(1, AnimeIndex::Season(1.0));
+        let known = FrontendAnimeEntry {
+            release_year: Some(2010),
+            score: Some(85),
+            ..entry(2, AnimeIndex::Season(1.0))
+        };
+
+        let filters = SongFilters {
+            year_min: Some(2000),
+            min_score: Some(80),
+            ..Default::default()
+        };
+        assert!(!filters.allows(&unknown));
+        assert!(filters.allows(&known));
+
+        let too_old = SongFilters {
+            year_max: Some(2005),
+            ..Default::default()
+        };
+        assert!(!too_old.allows(&known));
+    }
+
+    #[test]
+    fn sorts_missing_values_last() {
+        let mut animes = vec![
+            entry(1, AnimeIndex::Season(1.0)),
+            FrontendAnimeEntry {
+                score: Some(70),
+                ..entry(2, AnimeIndex::Season(1.0))
+            },
+            FrontendAnimeEntry {
+                score: Some(90),
+                ..entry(3, AnimeIndex::Season(1.0))
+            },
+        ];
+
+        SortKey::Score.sort(&mut animes, false);
+        assert_eq!(ids(&animes), vec![2, 3, 1]);
+
+        SortKey::Score.sort(&mut animes, true);
+        assert_eq!(ids(&animes), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn sorts_by_anime_index() {
+        let mut animes = vec![
+            entry(1, AnimeIndex::Movie(1.0)),
+            entry(2, AnimeIndex::Season(2.0)),
+            entry(3, AnimeIndex::Season(1.0)),
+        ];
+
+        SortKey::AnimeIndex.sort(&mut animes, false);
+        assert_eq!(ids(&animes), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn groups_by_franchise() {
+        let mut new_song = NewSong::Hit(SongHit {
+            song_info: SongInfo {
+                title: String::new(),
+                artists: vec![],
+                album_picture_url: String::new(),
+                spotify_id: String::new(),
+            },
+            certainty: 100,
+            anime_info: vec![
+                FrontendAnimeEntry {
+                    franchise_id: Some(7),
+                    ..entry(1, AnimeIndex::Season(2.0))
+                },
+                entry(2, AnimeIndex::Season(1.0)),
+                FrontendAnimeEntry {
+                    franchise_id: Some(7),
+                    ..entry(3, AnimeIndex::Season(1.0))
+                },
+                entry(4, AnimeIndex::Season(1.0)),
+            ],
+            more_with_artist: vec![],
+            franchises: None,
+        });
+
+        new_song.group_by_franchise();
+
+        let NewSong::Hit(hit) = new_song else {
+            panic!("Grouping turned a hit into a miss");
+        };
+        let groups = hit.franchises.unwrap();
+        assert!(hit.anime_info.is_empty());
+        assert_eq!(
+            groups
+                .iter()
+                .map(|g| (g.franchise_id, ids(&g.animes)))
+                .collect::<Vec<_>>(),
+            vec![(Some(7), vec![3, 1]), (None, vec![2]), (None, vec![4])]
+        );
+        // Named after the first season, not whichever matched first
+        assert_eq!(groups[0].title, "Anime 3");
+    }
+
+    #[test]
+    fn tags_indexes_only_for_v1() {
+        let legacy = serde_json::to_value(entry(1, AnimeIndex::Season(2.0))).unwrap();
+        assert_eq!(legacy["anime_index"], serde_json::json!({"Season": 2.0}));
+        assert_eq!(legacy["track_index"], serde_json::json!({"Opening": 1}));
+
+        let tagged = TAGGED_INDEXES.sync_scope((), || {
+            serde_json::to_value(entry(1, AnimeIndex::Season(2.0))).unwrap()
+        });
+        assert_eq!(
+            tagged["anime_index"],
+            serde_json::json!({"kind": "Season", "number": 2.0})
+        );
+        assert_eq!(
+            tagged["track_index"],
+            serde_json::json!({"kind": "Opening", "number": 1})
+        );
+    }
+}